@@ -56,14 +56,10 @@ impl sge::Application for App {
                 self.y = pos.y() as f64;
             }
 
-            tex.draw(
+            // Drawing at floating point coordinates keeps slow movement smooth
+            tex.draw_f(
                 None, // Src rect (None = entire texture)
-                Rect::new(
-                    self.x as i32,
-                    self.y as i32,
-                    tex.size().x() as u32,
-                    tex.size().y() as u32,
-                ),
+                FRect::new(self.x as f32, self.y as f32, size.0 as f32, size.1 as f32),
             )?;
         }
         Ok(true)
@@ -0,0 +1,45 @@
+use std::error::Error;
+
+const SCREEN_WIDTH: u32 = 160;
+const SCREEN_HEIGHT: u32 = 120;
+const PIXEL_SIZE: f32 = 4.0;
+
+struct App {
+    time: f32,
+}
+
+impl App {
+    pub fn new() -> Self {
+        Self { time: 0.0 }
+    }
+}
+
+impl sge::Application for App {
+    fn on_update(&mut self, elapsed_time: f64) -> sge::ApplicationResult {
+        self.time += elapsed_time as f32;
+        let t = self.time;
+        // Writing to the framebuffer directly is much faster than drawing every pixel
+        sge::with_framebuffer(|fb| {
+            let width = fb.width() as usize;
+            for (i, pixel) in fb.pixels().iter_mut().enumerate() {
+                let (x, y) = ((i % width) as f32 / 16.0, (i / width) as f32 / 16.0);
+                let v = (x + t).sin() + (y + t / 2.0).sin() + ((x + y + t) / 2.0).sin();
+                let channel = |offset: f32| ((v + offset).sin() * 127.0 + 128.0) as u32;
+                *pixel = 0xFF00_0000 | channel(0.0) << 16 | channel(2.0) << 8 | channel(4.0);
+            }
+        });
+        sge::draw_framebuffer()?;
+        Ok(true)
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut app = App::new();
+    sge::Builder::new(
+        "Plasma",
+        (SCREEN_WIDTH as f32 * PIXEL_SIZE) as u32,
+        (SCREEN_HEIGHT as f32 * PIXEL_SIZE) as u32,
+    )
+    .scale(PIXEL_SIZE, PIXEL_SIZE)
+    .start(&mut app)
+}
@@ -1,6 +1,6 @@
 use std::error::Error;
 
-use sge::{prelude::*, Event};
+use sge::prelude::*;
 
 const SCREEN_WIDTH: u32 = 480;
 const SCREEN_HEIGHT: u32 = 360;
@@ -18,7 +18,13 @@ impl App {
 }
 
 impl sge::Application for App {
+    fn on_create(&mut self) -> sge::ApplicationResult {
+        sge::start_text_input(None);
+        Ok(true)
+    }
+
     fn on_update(&mut self, elapsed_time: f64) -> sge::ApplicationResult {
+        self.text.push_str(&sge::text_input());
         if sge::key(Scancode::Backspace).pressed {
             self.text.pop();
         }
@@ -26,22 +32,15 @@ impl sge::Application for App {
             self.text.push('\n');
         }
         sge::clear(Color::BLACK);
-        for (i, line) in self.text.lines().enumerate() {
+        // Show any text being composed by an input method after the entered text
+        let composition = sge::text_composition().map(|c| c.text).unwrap_or_default();
+        let text = self.text.clone() + &composition;
+        for (i, line) in text.lines().enumerate() {
             // Note: this doesn't wrap lines, they'll just be cut off
             sge::draw_string((0, 8 * i as i32), line, Color::WHITE);
         }
         Ok(true)
     }
-
-    fn on_event(&mut self, event: &Event) -> sge::ApplicationResult {
-        match event {
-            Event::TextInput { ref text, .. } => {
-                self.text.push_str(text);
-                Ok(true) // We handled this event
-            }
-            _ => Ok(false), // Unhandled event
-        }
-    }
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
@@ -1,8 +1,16 @@
-use std::{cell::RefCell, error::Error};
+use std::{
+    cell::RefMut,
+    error::Error,
+    mem,
+    path::{Path, PathBuf},
+};
 
-use sdl2::event::Event;
+use sdl2::event::{Event, WindowEvent};
 
-use crate::{Application, Engine, Fullscreen, ENGINE, NOT_INIT};
+use crate::{
+    input::Scancode, with_engine_mut, Application, Color, Engine, Fullscreen, ScaleMode, ENGINE,
+    NOT_INIT,
+};
 
 /// A type used to construct and start an engine from an [`Application`][crate::Application].
 #[must_use = "Builders do nothing unless an Application is started with them"]
@@ -14,6 +22,17 @@ pub struct Builder<'a> {
     pub(crate) show_fps: bool,
     pub(crate) fullscreen: Fullscreen,
     pub(crate) scale: (f32, f32),
+    pub(crate) headless: bool,
+    pub(crate) max_frames: Option<u64>,
+    pub(crate) screenshot_key: Option<Scancode>,
+    pub(crate) fixed_timestep: Option<f64>,
+    pub(crate) max_fixed_steps: u32,
+    pub(crate) target_fps: Option<u32>,
+    pub(crate) logical_size: Option<(u32, u32, ScaleMode)>,
+    pub(crate) letterbox_color: Color,
+    pub(crate) resizable: bool,
+    pub(crate) record_input: Option<PathBuf>,
+    pub(crate) replay_input: Option<PathBuf>,
     #[cfg(feature = "gfx")]
     pub(crate) anti_alias: bool,
 }
@@ -29,6 +48,17 @@ impl<'a> Builder<'a> {
             show_fps: true,
             fullscreen: Fullscreen::Off,
             scale: (1.0, 1.0),
+            headless: false,
+            max_frames: None,
+            screenshot_key: None,
+            fixed_timestep: None,
+            max_fixed_steps: 5,
+            target_fps: None,
+            logical_size: None,
+            letterbox_color: Color::BLACK,
+            resizable: false,
+            record_input: None,
+            replay_input: None,
             #[cfg(feature = "gfx")]
             anti_alias: false,
         }
@@ -41,6 +71,14 @@ impl<'a> Builder<'a> {
         self
     }
 
+    /// Limit the application to `fps` frames per second, independently of vsync. This can be
+    /// changed while running with [`set_target_fps`][crate::set_target_fps]. A target of 0 means
+    /// no limit.
+    pub fn target_fps(mut self, fps: u32) -> Self {
+        self.target_fps = Some(fps).filter(|&fps| fps > 0);
+        self
+    }
+
     /// Set whether the FPS is calculated and shown in the Window's title-bar. Regardless of this
     /// setting, the elapsed time between frames will still be calculated and passed to
     /// [`Application::on_update`][crate::Application::on_update].
@@ -59,12 +97,133 @@ impl<'a> Builder<'a> {
     }
 
     /// Set the engine scale. All drawn objects will be scaled by this amount in each axis.
+    ///
+    /// This has no effect if a [`logical_size`](Self::logical_size) is set.
     pub fn scale(mut self, scale_x: f32, scale_y: f32) -> Self {
         self.scale.0 = scale_x;
         self.scale.1 = scale_y;
         self
     }
 
+    /// Set whether the engine runs headless. A headless engine never shows a window, and draws to
+    /// an offscreen software render target instead. This allows applications to be run in
+    /// environments without a display, such as automated tests and CI.
+    ///
+    /// The [`fullscreen`](Self::fullscreen) and [`present_vsync`](Self::present_vsync) settings
+    /// are ignored when running headless.
+    pub fn headless(mut self, val: bool) -> Self {
+        self.headless = val;
+        self
+    }
+
+    /// Stop the engine after `frames` frames have been run, as if the
+    /// [`Application`][crate::Application] had returned `Ok(false)`. This is mostly useful
+    /// together with [`headless`](Self::headless), to step an application a fixed number of
+    /// frames.
+    ///
+    /// With a limit of 0 frames, [`Application::on_create`][crate::Application::on_create] and
+    /// [`Application::on_destroy`][crate::Application::on_destroy] are still called, but no frames
+    /// are run in between.
+    pub fn max_frames(mut self, frames: u64) -> Self {
+        self.max_frames = Some(frames);
+        self
+    }
+
+    /// Use a fixed timestep, calling
+    /// [`Application::on_fixed_update`][crate::Application::on_fixed_update] `hz` times per
+    /// second regardless of the frame rate. Use [`interpolation_alpha`][crate::interpolation_alpha]
+    /// to render smoothly between fixed updates.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `hz` isn't a positive, finite number.
+    pub fn fixed_timestep(mut self, hz: f64) -> Self {
+        assert!(
+            hz > 0.0 && hz.is_finite(),
+            "Fixed timestep rate must be positive and finite, got {}",
+            hz
+        );
+        self.fixed_timestep = Some(hz);
+        self
+    }
+
+    /// Set the maximum number of fixed updates run in a single frame (5 by default). If a frame
+    /// takes so long that more would be due, the extra time is dropped, so the application can
+    /// catch up rather than falling further and further behind.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `steps` is 0, since no fixed updates would ever be run.
+    pub fn max_fixed_steps(mut self, steps: u32) -> Self {
+        assert!(
+            steps > 0,
+            "At least one fixed step per frame must be allowed"
+        );
+        self.max_fixed_steps = steps;
+        self
+    }
+
+    /// Set a key that saves a screenshot when pressed. Screenshots are saved in the working
+    /// directory, with a name containing the time they were taken. They are saved as PNG files if
+    /// the `image` feature is enabled, or BMP files otherwise.
+    ///
+    /// [`Application::on_screenshot`][crate::Application::on_screenshot] is called with the
+    /// result of saving each screenshot.
+    pub fn screenshot_key(mut self, key: Scancode) -> Self {
+        self.screenshot_key = Some(key);
+        self
+    }
+
+    /// Set a fixed logical resolution. Everything is drawn at this resolution, then scaled to fit
+    /// the window according to `mode`, even as the window is resized or made fullscreen. Any part
+    /// of the window not covered is filled with the [`letterbox_color`](Self::letterbox_color).
+    ///
+    /// The width and height passed to [`Builder::new`] are still used as the initial size of the
+    /// window, which is made resizable. The mouse position is reported in logical coordinates.
+    pub fn logical_size(mut self, width: u32, height: u32, mode: ScaleMode) -> Self {
+        self.logical_size = Some((width, height, mode));
+        self.resizable = true;
+        self
+    }
+
+    /// Set the color of the bars around the logical area set with
+    /// [`logical_size`](Self::logical_size). Black by default.
+    pub fn letterbox_color<C: Into<Color>>(mut self, color: C) -> Self {
+        self.letterbox_color = color.into();
+        self
+    }
+
+    /// Set whether the window can be resized by the user.
+    pub fn resizable(mut self, val: bool) -> Self {
+        self.resizable = val;
+        self
+    }
+
+    /// Record the keyboard and mouse input and elapsed time of every frame to a file, which can
+    /// be replayed with [`replay_input`](Self::replay_input). Any existing file is overwritten.
+    ///
+    /// If writing to the file fails, the application stops and the error is returned from
+    /// [`start`](Self::start).
+    pub fn record_input<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.record_input = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Replay a recording made with [`record_input`](Self::record_input). The recorded keyboard
+    /// and mouse input is used instead of the real input, and the recorded elapsed time is passed
+    /// to [`Application::on_update`][crate::Application::on_update], so an application that only
+    /// depends on these behaves exactly as it did when it was recorded. The engine stops at the
+    /// end of the recording.
+    ///
+    /// Keyboard, mouse and text input events from the real devices are dropped, and
+    /// [`Application::on_event`][crate::Application::on_event] receives events generated from the
+    /// recording instead. Other events, such as window events, are still delivered. This
+    /// overrides `record_input`.
+    pub fn replay_input<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.replay_input = Some(path.as_ref().to_path_buf());
+        self
+    }
+
     /// Set whether drawn shapes are anti-aliased. Doing so smooths the edges of objects, but is
     /// more CPU intensive.
     #[cfg(feature = "gfx")]
@@ -75,55 +234,399 @@ impl<'a> Builder<'a> {
 
     // Start the engine, using the settings from this `Builder`, and running the passed
     // [`Application`][crate::Application].
+    //
+    // Once the application quits, the engine is destroyed, so another can be started in the same
+    // thread.
     pub fn start<A: Application>(self, app: &mut A) -> Result<(), Box<dyn Error>> {
-        let title = self.title;
-        let show_fps = self.show_fps;
-
-        ENGINE.with(|e| {
-            use crate::fps::FpsCounter;
-
-            let mut fps_counter: FpsCounter;
-            {
-                let new = Engine::new(self)?;
-                fps_counter = FpsCounter::new(new.sdl.timer()?);
-                if e.set(RefCell::new(new)).is_err() {
-                    panic!("An engine was already started in this thread");
-                }
+        let max_frames = self.max_frames;
+
+        ENGINE.with(|e| -> Result<(), Box<dyn Error>> {
+            let mut engine = e.borrow_mut();
+            if engine.is_some() {
+                panic!("An engine was already started in this thread");
             }
-            if app.on_create()? {
-                loop {
-                    let elapsed_time = fps_counter.update(show_fps);
-                    {
-                        let mut engine = e.get().expect(NOT_INIT).borrow_mut();
+            *engine = Some(Engine::new(self)?);
+            Ok(())
+        })?;
+        // Make sure the engine is destroyed, even if the application panics
+        let _teardown = Teardown;
 
-                        if show_fps && fps_counter.time_acc() >= 1.0 {
-                            let fps = fps_counter.fps();
-                            let title = format!("{} ({:.0} FPS)", title, fps.round());
+        let res = run(app, max_frames);
+        let flushed = with_engine_mut(Engine::flush_recording);
+        let destroyed = app.on_destroy();
+        res.and(flushed.map_err(Into::into)).and(destroyed)
+    }
+}
 
-                            // This fails silently on error
-                            engine.canvas.window_mut().set_title(&title).ok();
+/// Destroys the engine running in this thread when dropped.
+struct Teardown;
 
-                            fps_counter.reset_average();
+impl Drop for Teardown {
+    fn drop(&mut self) {
+        // Take the engine out first, so it isn't borrowed while being dropped
+        let engine = ENGINE.with(|e| e.try_borrow_mut().ok().and_then(|mut e| e.take()));
+        if let Some(ref engine) = engine {
+            crate::texture::destroy_dropped(engine);
+        }
+        drop(engine);
+    }
+}
+
+/// Run the main loop of an application on the engine running in this thread.
+fn run<A: Application>(app: &mut A, max_frames: Option<u64>) -> Result<(), Box<dyn Error>> {
+    ENGINE.with(|e| {
+        if app.on_create()? {
+            let mut frames = 0;
+            while !matches!(max_frames, Some(max) if frames >= max) {
+                let mut elapsed_time;
+                let frame;
+                {
+                    let mut engine = RefMut::map(e.borrow_mut(), |e| e.as_mut().expect(NOT_INIT));
+
+                    if let Some(fps) = engine.target_fps {
+                        engine.fps.limit(fps);
+                    }
+                    elapsed_time = engine.fps.update();
+
+                    if engine.show_fps && engine.fps.report_due(1.0) {
+                        // This fails silently on error
+                        engine.refresh_title().ok();
+                    }
+
+                    frame = match engine.update(&mut elapsed_time)? {
+                        Some(frame) => frame,
+                        None => return Ok(()), // The replayed recording ended
+                    };
+                }
+
+                // The engine isn't borrowed here, so event handlers can use SGE functions
+                if let Some(result) = frame.screenshot {
+                    if !app.on_screenshot(result)? {
+                        return Ok(()); // Application wanted to quit
+                    }
+                }
+                for event in &frame.events {
+                    if let Event::Window {
+                        win_event: WindowEvent::SizeChanged(width, height),
+                        ..
+                    } = *event
+                    {
+                        if !app.on_resize(width as u32, height as u32)? {
+                            return Ok(()); // Application wanted to quit
+                        }
+                    }
+                    if !app.on_event(event)? {
+                        // The app didn't handle the event
+                        if let Event::Quit { .. } = event {
+                            with_engine_mut(|e| e.quit_requested = true);
                         }
+                    }
+                }
 
-                        engine.update();
+                let fixed_steps = with_engine_mut(|e| {
+                    e.timestep
+                        .as_mut()
+                        .map(|t| (t.advance(elapsed_time), t.step()))
+                });
 
-                        for event in engine.events.poll_iter() {
-                            if !app.on_event(&event)? {
-                                // The app didn't handle the event
-                                if let Event::Quit { .. } = event {
-                                    return Ok(()); // Quit
-                                }
-                            }
+                if quit_confirmed(app)? {
+                    return Ok(()); // Quit
+                }
+
+                if let Some((steps, step)) = fixed_steps {
+                    for _ in 0..steps {
+                        if !app.on_fixed_update(step)? {
+                            return Ok(()); // Application wanted to quit
                         }
                     }
+                }
 
-                    if !app.on_update(elapsed_time)? {
-                        return Ok(()); // Application wanted to quit
+                if !app.on_update(elapsed_time)? || quit_confirmed(app)? {
+                    return Ok(()); // Application wanted to quit
+                }
+
+                frames += 1;
+            }
+        }
+        Ok(())
+    })
+}
+
+/// If a quit was requested, ask the application whether to go ahead with it.
+fn quit_confirmed<A: Application>(app: &mut A) -> Result<bool, Box<dyn Error>> {
+    if with_engine_mut(|e| mem::take(&mut e.quit_requested)) {
+        Ok(!app.on_quit_requested()?)
+    } else {
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A headless engine runs exactly the requested number of frames.
+    #[test]
+    fn test_headless_max_frames() {
+        crate::test_util::run(|| {
+            let mut frames = 0;
+            let mut app = |_| {
+                crate::clear(crate::Color::BLACK);
+                frames += 1;
+                Ok(true)
+            };
+            Builder::new("Test", 64, 64)
+                .headless(true)
+                .max_frames(5)
+                .start(&mut app)
+                .unwrap();
+            assert_eq!(frames, 5);
+        });
+    }
+
+    /// A limit of 0 frames creates and destroys the application without running any frames.
+    #[test]
+    fn test_headless_zero_frames() {
+        #[derive(Default)]
+        struct App {
+            created: bool,
+            frames: u32,
+            destroyed: bool,
+        }
+
+        impl Application for App {
+            fn on_create(&mut self) -> Result<bool, Box<dyn Error>> {
+                self.created = true;
+                Ok(true)
+            }
+
+            fn on_update(&mut self, _elapsed_time: f64) -> Result<bool, Box<dyn Error>> {
+                self.frames += 1;
+                Ok(true)
+            }
+
+            fn on_destroy(&mut self) -> Result<(), Box<dyn Error>> {
+                self.destroyed = true;
+                Ok(())
+            }
+        }
+
+        crate::test_util::run(|| {
+            let mut app = App::default();
+            Builder::new("Test", 64, 64)
+                .headless(true)
+                .max_frames(0)
+                .start(&mut app)
+                .unwrap();
+            assert!(app.created && app.destroyed);
+            assert_eq!(app.frames, 0);
+        });
+    }
+
+    /// Fixed timesteps that would never run an update are rejected.
+    #[test]
+    #[should_panic]
+    fn test_fixed_timestep_zero() {
+        let _ = Builder::new("Test", 64, 64).fixed_timestep(0.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_max_fixed_steps_zero() {
+        let _ = Builder::new("Test", 64, 64).max_fixed_steps(0);
+    }
+
+    /// `on_destroy` runs with the engine still alive, and another engine can be started in the
+    /// same thread afterwards.
+    #[test]
+    fn test_restart() {
+        struct App {
+            destroyed: bool,
+        }
+
+        impl Application for App {
+            fn on_destroy(&mut self) -> Result<(), Box<dyn Error>> {
+                crate::clear(crate::Color::BLACK);
+                self.destroyed = true;
+                Ok(())
+            }
+        }
+
+        crate::test_util::run(|| {
+            for _ in 0..2 {
+                let mut app = App { destroyed: false };
+                Builder::new("Test", 64, 64)
+                    .headless(true)
+                    .max_frames(1)
+                    .start(&mut app)
+                    .unwrap();
+                assert!(app.destroyed);
+            }
+        });
+    }
+
+    /// Quit requests can be cancelled by the application.
+    #[test]
+    fn test_request_quit() {
+        struct App {
+            frames: u32,
+            vetoes: u32,
+        }
+
+        impl Application for App {
+            fn on_update(&mut self, _elapsed_time: f64) -> crate::ApplicationResult {
+                self.frames += 1;
+                crate::request_quit();
+                Ok(true)
+            }
+
+            fn on_quit_requested(&mut self) -> crate::ApplicationResult {
+                self.vetoes += 1;
+                Ok(self.vetoes < 3)
+            }
+        }
+
+        crate::test_util::run(|| {
+            let mut app = App {
+                frames: 0,
+                vetoes: 0,
+            };
+            Builder::new("Test", 64, 64)
+                .headless(true)
+                .max_frames(10)
+                .start(&mut app)
+                .unwrap();
+            assert_eq!(app.frames, 3);
+        });
+    }
+
+    /// SGE functions can be called from event handlers.
+    #[test]
+    fn test_on_event_uses_engine() {
+        struct App {
+            handled: bool,
+        }
+
+        impl Application for App {
+            fn on_create(&mut self) -> crate::ApplicationResult {
+                let events = crate::with_engine(|e| e.sdl().event())?;
+                events.push_event(Event::Quit { timestamp: 0 })?;
+                Ok(true)
+            }
+
+            fn on_event(&mut self, event: &Event) -> crate::ApplicationResult {
+                if let Event::Quit { .. } = event {
+                    crate::clear(crate::Color::BLACK);
+                    crate::key(Scancode::Space);
+                    self.handled = true;
+                    return Ok(true);
+                }
+                Ok(false)
+            }
+        }
+
+        crate::test_util::run(|| {
+            let mut app = App { handled: false };
+            Builder::new("Test", 64, 64)
+                .headless(true)
+                .max_frames(2)
+                .start(&mut app)
+                .unwrap();
+            assert!(app.handled);
+        });
+    }
+
+    /// Failing to write a recording stops the application with an error.
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_record_error() {
+        crate::test_util::run(|| {
+            let mut app = |_| Ok(true);
+            let res = Builder::new("Test", 64, 64)
+                .headless(true)
+                .max_frames(2)
+                .record_input("/dev/full")
+                .start(&mut app);
+            assert!(res.is_err());
+        });
+    }
+
+    /// A replayed recording reproduces the recorded input and elapsed time, then stops.
+    #[test]
+    fn test_record_replay() {
+        let path = std::env::temp_dir().join("sge-test-record-replay.bin");
+        let mut recorded = Vec::new();
+        let mut replayed = Vec::new();
+
+        crate::test_util::run(|| {
+            let mut frame = 0;
+            let mut app = |elapsed_time| {
+                recorded.push((crate::key(Scancode::Space).held, elapsed_time));
+                // Visible from the next frame
+                let held = frame == 1;
+                with_engine_mut(|e| e.input.injected.keys.insert(Scancode::Space, held));
+                frame += 1;
+                Ok(true)
+            };
+            Builder::new("Test", 64, 64)
+                .headless(true)
+                .max_frames(4)
+                .record_input(&path)
+                .start(&mut app)
+                .unwrap();
+
+            struct Replay<'a> {
+                replayed: &'a mut Vec<(bool, f64)>,
+                key_events: Vec<Event>,
+            }
+
+            impl Application for Replay<'_> {
+                fn on_update(&mut self, elapsed_time: f64) -> Result<bool, Box<dyn Error>> {
+                    let held = crate::key(Scancode::Space).held;
+                    self.replayed.push((held, elapsed_time));
+                    Ok(true)
+                }
+
+                fn on_event(&mut self, event: &Event) -> Result<bool, Box<dyn Error>> {
+                    if let Event::KeyDown { .. } | Event::KeyUp { .. } = event {
+                        self.key_events.push(event.clone());
                     }
+                    Ok(false)
                 }
             }
-            Ok(())
-        })
+
+            let mut app = Replay {
+                replayed: &mut replayed,
+                key_events: Vec::new(),
+            };
+            Builder::new("Test", 64, 64)
+                .headless(true)
+                .max_frames(10)
+                .replay_input(&path)
+                .start(&mut app)
+                .unwrap();
+
+            // The recorded key press is delivered as events
+            let keys: Vec<_> = app
+                .key_events
+                .iter()
+                .map(|e| match *e {
+                    Event::KeyDown { scancode, .. } => (scancode, true),
+                    Event::KeyUp { scancode, .. } => (scancode, false),
+                    _ => unreachable!(),
+                })
+                .collect();
+            assert_eq!(
+                keys,
+                vec![
+                    (Some(Scancode::Space), true),
+                    (Some(Scancode::Space), false)
+                ]
+            );
+        });
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(recorded.iter().filter(|(held, _)| *held).count(), 1);
+        assert_eq!(replayed, recorded);
     }
 }
@@ -1,6 +1,6 @@
 use sdl2::render::BlendMode;
 
-use crate::{with_engine, with_engine_mut, Color, Point, Rect};
+use crate::{transform, with_engine, with_engine_mut, Color, Point, Rect};
 
 /// Get the current blend mode.
 pub fn blend_mode() -> BlendMode {
@@ -29,8 +29,11 @@ where
     C: Into<Color>,
 {
     with_engine_mut(|engine| {
+        let t = transform::current(engine);
         engine.canvas.set_draw_color(color);
-        engine.canvas.draw_line(start, end)
+        engine
+            .canvas
+            .draw_line(t.apply_point(start.into()), t.apply_point(end.into()))
     })
 }
 
@@ -41,8 +44,10 @@ where
     C: Into<Color>,
 {
     with_engine_mut(|engine| {
+        let t = transform::current(engine);
+        let points: Vec<Point> = points.into().iter().map(|&p| t.apply_point(p)).collect();
         engine.canvas.set_draw_color(color);
-        engine.canvas.draw_lines(points)
+        engine.canvas.draw_lines(points.as_slice())
     })
 }
 
@@ -53,8 +58,9 @@ where
     C: Into<Color>,
 {
     with_engine_mut(|engine| {
+        let t = transform::current(engine);
         engine.canvas.set_draw_color(color);
-        engine.canvas.draw_point(point)
+        engine.canvas.draw_point(t.apply_point(point.into()))
     })
 }
 
@@ -65,8 +71,10 @@ where
     C: Into<Color>,
 {
     with_engine_mut(|engine| {
+        let t = transform::current(engine);
+        let points: Vec<Point> = points.into().iter().map(|&p| t.apply_point(p)).collect();
         engine.canvas.set_draw_color(color);
-        engine.canvas.draw_points(points)
+        engine.canvas.draw_points(points.as_slice())
     })
 }
 
@@ -77,8 +85,9 @@ where
     C: Into<Color>,
 {
     with_engine_mut(|engine| {
+        let t = transform::current(engine);
         engine.canvas.set_draw_color(color);
-        engine.canvas.draw_rect(rect.into())
+        transform::draw_rects(&mut engine.canvas, t, &[rect.into()])
     })
 }
 
@@ -89,8 +98,9 @@ where
     C: Into<Color>,
 {
     with_engine_mut(|engine| {
+        let t = transform::current(engine);
         engine.canvas.set_draw_color(color);
-        engine.canvas.draw_rects(rects.into())
+        transform::draw_rects(&mut engine.canvas, t, rects.into())
     })
 }
 
@@ -101,8 +111,9 @@ where
     C: Into<Color>,
 {
     with_engine_mut(|engine| {
+        let t = transform::current(engine);
         engine.canvas.set_draw_color(color);
-        engine.canvas.fill_rect(rect.into())
+        transform::fill_rects(&mut engine.canvas, t, &[rect.into()])
     })
 }
 
@@ -113,8 +124,9 @@ where
     C: Into<Color>,
 {
     with_engine_mut(|engine| {
+        let t = transform::current(engine);
         engine.canvas.set_draw_color(color);
-        engine.canvas.fill_rects(rects.into())
+        transform::fill_rects(&mut engine.canvas, t, rects.into())
     })
 }
 
@@ -129,11 +141,124 @@ pub fn set_clip_rect<R: Into<Option<Rect>>>(rect: R) {
 }
 
 /// Set or clear the current viewport. Any draw calls will be confined to this viewport until it is reset.
+///
+/// If a [logical size][crate::Builder::logical_size] is in use, the viewport is relative to the
+/// logical area, and clearing it restores the whole logical area. The viewport stays in the same
+/// place within the logical area when the window is resized. While drawing to a texture with
+/// [`with_target`][crate::with_target], the viewport is relative to the texture.
 pub fn set_viewport<R: Into<Option<Rect>>>(rect: R) {
-    with_engine_mut(|engine| engine.canvas.set_viewport(rect))
+    let rect = rect.into();
+    with_engine_mut(|engine| {
+        let drawing_to_window = engine.logical_area().is_some();
+        match engine.logical {
+            Some(ref mut logical) if drawing_to_window => {
+                logical.viewport = rect;
+                let rect = logical.viewport_rect(rect);
+                engine.canvas.set_viewport(rect)
+            }
+            _ => engine.canvas.set_viewport(rect),
+        }
+    })
 }
 
 /// Get the current viewport (if any).
 pub fn viewport() -> Rect {
-    with_engine(|e| e.canvas.viewport())
+    with_engine(|e| {
+        let rect = e.canvas.viewport();
+        match e.logical_area() {
+            Some(area) => Rect::new(
+                rect.x() - area.x(),
+                rect.y() - area.y(),
+                rect.width(),
+                rect.height(),
+            ),
+            None => rect,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+
+    /// Filled rectangles cover exactly their area.
+    #[test]
+    fn test_fill_rect() {
+        test_util::check_frame(
+            16,
+            16,
+            || {
+                clear(Color::BLACK);
+                fill_rect(Rect::new(4, 4, 8, 8), Color::GREEN)
+            },
+            |shot| {
+                assert_eq!(shot.pixel(4, 4), Some(Color::GREEN));
+                assert_eq!(shot.pixel(11, 11), Some(Color::GREEN));
+                assert_eq!(shot.pixel(3, 4), Some(Color::BLACK));
+                assert_eq!(shot.pixel(12, 11), Some(Color::BLACK));
+            },
+        );
+    }
+
+    /// Rectangle outlines don't fill their interior.
+    #[test]
+    fn test_draw_rect() {
+        test_util::check_frame(
+            16,
+            16,
+            || {
+                clear(Color::BLACK);
+                draw_rect(Rect::new(4, 4, 8, 8), Color::BLUE)
+            },
+            |shot| {
+                assert_eq!(shot.pixel(4, 4), Some(Color::BLUE));
+                assert_eq!(shot.pixel(11, 7), Some(Color::BLUE));
+                assert_eq!(shot.pixel(7, 7), Some(Color::BLACK));
+            },
+        );
+    }
+
+    /// Drawing is confined to the clipping rectangle.
+    #[test]
+    fn test_clip_rect() {
+        test_util::check_frame(
+            16,
+            16,
+            || {
+                clear(Color::BLACK);
+                set_clip_rect(Rect::new(0, 0, 8, 16));
+                fill_rect(Rect::new(0, 0, 16, 16), Color::WHITE)
+            },
+            |shot| {
+                assert_eq!(shot.pixel(7, 0), Some(Color::WHITE));
+                assert_eq!(shot.pixel(8, 0), Some(Color::BLACK));
+            },
+        );
+    }
+
+    /// The viewport keeps its place in the logical area when the window is resized.
+    #[test]
+    fn test_viewport_resize() {
+        test_util::run(|| {
+            let mut frame = 0;
+            let mut app = |_| {
+                if frame == 0 {
+                    set_viewport(Rect::new(1, 1, 2, 2));
+                    crate::set_window_size(8, 4)?;
+                } else {
+                    assert_eq!(viewport(), Rect::new(1, 1, 2, 2));
+                }
+                frame += 1;
+                Ok(true)
+            };
+            crate::Builder::new("Test", 4, 4)
+                .headless(true)
+                .logical_size(4, 4, crate::ScaleMode::Letterbox)
+                .max_frames(2)
+                .start(&mut app)
+                .unwrap();
+            assert_eq!(frame, 2);
+        });
+    }
 }
@@ -1,4 +1,4 @@
-use std::{error::Error, fmt};
+use std::{error::Error, fmt, io};
 
 use sdl2::{video::WindowBuildError, IntegerOrSdlError};
 
@@ -7,6 +7,8 @@ pub enum EngineBuildError {
     Canvas(IntegerOrSdlError),
     Window(WindowBuildError),
     Sdl(String),
+    /// The input recording couldn't be created, or the recording to replay couldn't be read.
+    Recording(io::Error),
 }
 
 impl From<String> for EngineBuildError {
@@ -21,6 +23,7 @@ impl fmt::Display for EngineBuildError {
             Self::Canvas(e) => write!(f, "Error constructing canvas: {}", e),
             Self::Window(e) => write!(f, "Error constructing window: {}", e),
             Self::Sdl(s) => write!(f, "SDL error: {}", s),
+            Self::Recording(e) => write!(f, "Error opening input recording: {}", e),
         }
     }
 }
@@ -31,6 +34,7 @@ impl Error for EngineBuildError {
             Self::Canvas(e) => Some(e),
             Self::Window(e) => Some(e),
             Self::Sdl(_) => None,
+            Self::Recording(e) => Some(e),
         }
     }
 }
@@ -1,18 +1,51 @@
+use std::{
+    collections::BTreeMap,
+    env,
+    io::{self, Write},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use sdl2::event::WindowEvent;
+
 use crate::{
-    input::{InputState, KeyboardState, MouseState},
-    Builder, Fullscreen,
+    fps::FpsCounter,
+    framebuffer::FramebufferState,
+    input::{InputState, Recording, Scancode, Snapshot},
+    layer::{self, LayerState},
+    scaling::Logical,
+    screenshot, texture,
+    timestep::FixedTimestep,
+    transform::Transforms,
+    Builder, Event, Fullscreen,
 };
 
 mod error;
 pub use error::EngineBuildError;
 
+/// The ID to give the next engine that is created.
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
 pub struct Engine {
+    pub(crate) id: u64,
     pub(crate) sdl: sdl2::Sdl,
     pub(crate) video: sdl2::VideoSubsystem,
     pub(crate) canvas: sdl2::render::WindowCanvas,
     pub(crate) texture_creator: sdl2::render::TextureCreator<sdl2::video::WindowContext>,
     pub(crate) events: sdl2::EventPump,
     pub(crate) input: InputState,
+    pub(crate) headless: bool,
+    pub(crate) screenshot_key: Option<Scancode>,
+    pub(crate) timestep: Option<FixedTimestep>,
+    pub(crate) fps: FpsCounter,
+    pub(crate) target_fps: Option<u32>,
+    pub(crate) quit_requested: bool,
+    pub(crate) title: String,
+    pub(crate) show_fps: bool,
+    pub(crate) logical: Option<Logical>,
+    pub(crate) recording: Option<Recording>,
+    pub(crate) framebuffer: Option<FramebufferState>,
+    pub(crate) layers: BTreeMap<String, LayerState>,
+    pub(crate) transforms: Transforms,
     #[cfg(feature = "gfx")]
     pub(crate) anti_alias: bool,
 }
@@ -22,47 +55,93 @@ impl Engine {
         use EngineBuildError as E;
 
         let sdl = sdl2::init()?;
-        let video = sdl.video()?;
-        let mut canvas = video.window(builder.title, builder.width, builder.height);
+        let video = if builder.headless {
+            init_headless_video(&sdl)?
+        } else {
+            sdl.video()?
+        };
+        let mut window = video.window(builder.title, builder.width, builder.height);
 
-        match builder.fullscreen {
-            Fullscreen::Off => {} // Do nothing
-            Fullscreen::On => {
-                canvas.fullscreen();
-            }
-            Fullscreen::Desktop => {
-                canvas.fullscreen_desktop();
+        if builder.resizable {
+            window.resizable();
+        }
+        if builder.headless {
+            window.hidden();
+        } else {
+            match builder.fullscreen {
+                Fullscreen::Off => {} // Do nothing
+                Fullscreen::On => {
+                    window.fullscreen();
+                }
+                Fullscreen::Desktop => {
+                    window.fullscreen_desktop();
+                }
             }
         }
 
-        let mut canvas = canvas
+        let canvas = window
             .position_centered()
             .build()
             .map_err(E::Window)?
-            .into_canvas()
-            .accelerated();
+            .into_canvas();
 
-        if builder.present_vsync {
-            canvas = canvas.present_vsync();
-        }
+        let canvas = if builder.headless {
+            // There's no display to synchronise with, and no guarantee of a GPU
+            canvas.software()
+        } else if builder.present_vsync {
+            canvas.accelerated().present_vsync()
+        } else {
+            canvas.accelerated()
+        };
 
         let mut canvas = canvas.build().map_err(E::Canvas)?;
-        canvas.set_scale(builder.scale.0, builder.scale.1)?;
+        let logical = match builder.logical_size {
+            Some((width, height, mode)) => {
+                let mut logical = Logical::new((width, height), mode, builder.letterbox_color);
+                logical.apply(&mut canvas)?;
+                Some(logical)
+            }
+            None => {
+                canvas.set_scale(builder.scale.0, builder.scale.1)?;
+                None
+            }
+        };
         let texture_creator = canvas.texture_creator();
 
         let events = sdl.event_pump()?;
-        let input = InputState {
-            keyboard: KeyboardState::new(events.keyboard_state().scancodes()),
-            mouse: MouseState::new(events.mouse_state()),
+        // Controllers are optional, so the engine runs without them if they aren't supported
+        let input = InputState::new(&events, sdl.game_controller());
+        let fps = FpsCounter::new(sdl.timer()?);
+
+        let recording = match (&builder.replay_input, &builder.record_input) {
+            (Some(path), _) => Some(Recording::replay(path).map_err(E::Recording)?),
+            (None, Some(path)) => Some(Recording::record(path).map_err(E::Recording)?),
+            (None, None) => None,
         };
 
         Ok(Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
             sdl,
             video,
             canvas,
             texture_creator,
             events,
             input,
+            headless: builder.headless,
+            screenshot_key: builder.screenshot_key,
+            timestep: builder
+                .fixed_timestep
+                .map(|hz| FixedTimestep::new(hz, builder.max_fixed_steps)),
+            fps,
+            target_fps: builder.target_fps,
+            quit_requested: false,
+            title: builder.title.to_string(),
+            show_fps: builder.show_fps,
+            logical,
+            recording,
+            framebuffer: None,
+            layers: BTreeMap::new(),
+            transforms: Transforms::default(),
             #[cfg(feature = "gfx")]
             anti_alias: builder.anti_alias,
         })
@@ -84,11 +163,164 @@ impl Engine {
         &self.video
     }
 
-    pub fn update(&mut self) {
+    /// Returns whether this engine is running headless (without a visible window).
+    #[inline]
+    pub fn is_headless(&self) -> bool {
+        self.headless
+    }
+
+    /// Set the window's title from the stored title, adding the FPS if it is being shown.
+    pub(crate) fn refresh_title(&mut self) -> Result<(), String> {
+        let stats = self.fps.stats();
+        let title = if self.show_fps && stats.total_frames > 0 {
+            format!("{} ({:.0} FPS)", self.title, stats.fps.round())
+        } else {
+            self.title.clone()
+        };
+        self.canvas
+            .window_mut()
+            .set_title(&title)
+            .map_err(|e| e.to_string())
+    }
+
+    /// Get the size of the area drawn to, in drawing coordinates. This is the logical size if one
+    /// is set, or the canvas' output size divided by its scale otherwise.
+    pub(crate) fn drawing_size(&self) -> Result<(u32, u32), String> {
+        if let Some(ref logical) = self.logical {
+            return Ok(logical.size);
+        }
+        let (width, height) = self.canvas.output_size()?;
+        let (scale_x, scale_y) = self.canvas.scale();
+        Ok((
+            (width as f32 / scale_x).round() as u32,
+            (height as f32 / scale_y).round() as u32,
+        ))
+    }
+
+    /// Get the area of the window that the logical size is drawn to, if a logical size is set and
+    /// drawing isn't directed to a texture.
+    pub(crate) fn logical_area(&self) -> Option<sdl2::rect::Rect> {
+        match self.logical {
+            Some(ref logical) if !texture::target_set(self) => Some(logical.fit.viewport),
+            _ => None,
+        }
+    }
+
+    /// Write any buffered input to the file, if the input is being recorded.
+    pub(crate) fn flush_recording(&mut self) -> io::Result<()> {
+        match self.recording {
+            Some(Recording::Record(ref mut file)) => file.flush().map_err(recording_error),
+            _ => Ok(()),
+        }
+    }
+
+    /// Present the frame that was just drawn, then update the input state and return the events
+    /// that have arrived since the last frame. Called internally by the engine every frame.
+    ///
+    /// When replaying a recording, `elapsed_time` is replaced by the recorded time, and `None` is
+    /// returned once the recording has ended. An error is returned if recording the input fails.
+    pub(crate) fn update(&mut self, elapsed_time: &mut f64) -> io::Result<Option<Frame>> {
+        texture::destroy_dropped(self);
+        if let Some(ref logical) = self.logical {
+            // This fails silently on error
+            logical.draw_bars(&mut self.canvas).ok();
+        }
+
+        // The key state is from the frame that was just drawn. Screenshots include the layers
+        // themselves, so are taken before they are composited.
+        let screenshot = match self.screenshot_key {
+            Some(key) if self.input.keyboard[key].pressed => {
+                Some(screenshot::save_timestamped(self))
+            }
+            _ => None,
+        };
+        // This fails silently on error
+        layer::composite(self).ok();
+
         self.canvas.present();
-        self.input
-            .keyboard
-            .update(self.events.keyboard_state().scancodes());
-        self.input.mouse.update(self.events.mouse_state());
+        self.transforms.reset();
+        // Polling pumps the event loop, which brings the input state up to date
+        let mut events: Vec<Event> = self.events.poll_iter().collect();
+        let window_id = self.canvas.window().id();
+        match self.recording {
+            Some(Recording::Replay(ref mut snapshots)) => {
+                let snapshot = match snapshots.pop_front() {
+                    Some(snapshot) => snapshot,
+                    None => return Ok(None),
+                };
+                *elapsed_time = snapshot.elapsed_time;
+                self.input.replay(&snapshot, &mut events, window_id);
+            }
+            _ => self.input.update(&self.events, &mut events, window_id),
+        }
+        if let Some(Recording::Record(ref mut file)) = self.recording {
+            Snapshot::capture(&self.input, *elapsed_time)
+                .write(file)
+                .map_err(recording_error)?;
+        }
+
+        let resized = events.iter().any(|e| {
+            matches!(
+                e,
+                Event::Window {
+                    win_event: WindowEvent::SizeChanged(..),
+                    ..
+                }
+            )
+        });
+        match (resized, &mut self.logical) {
+            (true, Some(logical)) => {
+                // This fails silently on error, leaving the previous scaling in place
+                logical.apply(&mut self.canvas).ok();
+            }
+            (true, None) => {
+                // This fails silently on error, leaving layers that failed at their previous size
+                layer::resize(self).ok();
+            }
+            (false, _) => {}
+        }
+
+        Ok(Some(Frame { events, screenshot }))
     }
 }
+
+/// What happened since the last frame, returned by [`Engine::update`].
+pub(crate) struct Frame {
+    /// The events that arrived since the last frame.
+    pub events: Vec<Event>,
+    /// Where the screenshot taken with the screenshot key was saved, or why it couldn't be.
+    pub screenshot: Option<Result<String, String>>,
+}
+
+fn recording_error(e: io::Error) -> io::Error {
+    io::Error::new(e.kind(), format!("Failed to record input: {}", e))
+}
+
+/// Initialise the video subsystem with SDL's dummy video driver, which renders to an offscreen
+/// framebuffer and needs no display.
+///
+/// If the user has explicitly chosen a video driver through the `SDL_VIDEODRIVER` environment
+/// variable, it is respected.
+fn init_headless_video(sdl: &sdl2::Sdl) -> Result<sdl2::VideoSubsystem, String> {
+    const VIDEO_DRIVER: &str = "SDL_VIDEODRIVER";
+
+    if env::var_os(VIDEO_DRIVER).is_some() {
+        return sdl.video();
+    }
+    let version = sdl2::version::version();
+    if (version.major, version.minor, version.patch) < (2, 0, 22) {
+        // Versions of SDL before 2.0.22 only read the driver from the environment. Changing it
+        // isn't thread safe, but there is no other way to choose the driver.
+        env::set_var(VIDEO_DRIVER, "dummy");
+        let video = sdl.video();
+        env::remove_var(VIDEO_DRIVER);
+        return video;
+    }
+
+    let previous = sdl2::hint::get(VIDEO_DRIVER);
+    sdl2::hint::set(VIDEO_DRIVER, "dummy");
+    let video = sdl.video();
+    // Don't affect any engines created later on. An empty driver name means the default.
+    sdl2::hint::set(VIDEO_DRIVER, previous.as_deref().unwrap_or(""));
+    video
+}
@@ -0,0 +1,320 @@
+use std::os::raw::c_int;
+
+use sdl2::{render::WindowCanvas, sys};
+
+use crate::{transform, with_engine_mut, Color, Point, Rect, Transform};
+
+/// A point with floating point coordinates, which can be between pixels.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FPoint {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl FPoint {
+    /// Create a point at (`x`, `y`).
+    pub fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+
+    pub(crate) fn raw(self) -> sys::SDL_FPoint {
+        sys::SDL_FPoint {
+            x: self.x,
+            y: self.y,
+        }
+    }
+}
+
+impl From<(f32, f32)> for FPoint {
+    fn from((x, y): (f32, f32)) -> Self {
+        Self::new(x, y)
+    }
+}
+
+impl From<Point> for FPoint {
+    fn from(p: Point) -> Self {
+        Self::new(p.x() as f32, p.y() as f32)
+    }
+}
+
+/// A rectangle with floating point position and size, which can be between pixels.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FRect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl FRect {
+    /// Create a rectangle with its top left corner at (`x`, `y`) and the given size.
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Get the point at the center of the rectangle.
+    pub fn center(&self) -> FPoint {
+        FPoint::new(self.x + self.width / 2.0, self.y + self.height / 2.0)
+    }
+
+    pub(crate) fn raw(self) -> sys::SDL_FRect {
+        sys::SDL_FRect {
+            x: self.x,
+            y: self.y,
+            w: self.width,
+            h: self.height,
+        }
+    }
+}
+
+impl From<(f32, f32, f32, f32)> for FRect {
+    fn from((x, y, width, height): (f32, f32, f32, f32)) -> Self {
+        Self::new(x, y, width, height)
+    }
+}
+
+impl From<Rect> for FRect {
+    fn from(r: Rect) -> Self {
+        Self::new(
+            r.x() as f32,
+            r.y() as f32,
+            r.width() as f32,
+            r.height() as f32,
+        )
+    }
+}
+
+/// Convert the return value of an SDL function to a `Result`.
+pub(crate) fn check(res: c_int) -> Result<(), String> {
+    if res == 0 {
+        Ok(())
+    } else {
+        Err(sdl2::get_error())
+    }
+}
+
+fn transform_points(t: Transform, points: &[FPoint]) -> Vec<sys::SDL_FPoint> {
+    points
+        .iter()
+        .map(|p| FPoint::from(t.apply(p.x, p.y)).raw())
+        .collect()
+}
+
+fn draw_lines_raw(canvas: &WindowCanvas, points: &[sys::SDL_FPoint]) -> Result<(), String> {
+    // Safety: The renderer is valid for as long as the engine exists.
+    check(unsafe {
+        sys::SDL_RenderDrawLinesF(canvas.raw(), points.as_ptr(), points.len() as c_int)
+    })
+}
+
+/// Draw a straight line between `start` and `end`, with the specified color. The ends can be
+/// between pixels.
+pub fn draw_line_f<P1, P2, C>(start: P1, end: P2, color: C) -> Result<(), String>
+where
+    P1: Into<FPoint>,
+    P2: Into<FPoint>,
+    C: Into<Color>,
+{
+    draw_lines_f(&[start.into(), end.into()][..], color)
+}
+
+/// Draw a series of lines in the specified color. The points can be between pixels.
+pub fn draw_lines_f<'a, P, C>(points: P, color: C) -> Result<(), String>
+where
+    P: Into<&'a [FPoint]>,
+    C: Into<Color>,
+{
+    with_engine_mut(|engine| {
+        let points = transform_points(transform::current(engine), points.into());
+        engine.canvas.set_draw_color(color);
+        draw_lines_raw(&engine.canvas, &points)
+    })
+}
+
+/// Draw a single pixel at `point` with the specified color. The point can be between pixels.
+pub fn draw_point_f<P, C>(point: P, color: C) -> Result<(), String>
+where
+    P: Into<FPoint>,
+    C: Into<Color>,
+{
+    draw_points_f(&[point.into()][..], color)
+}
+
+/// Draw a series of points in the specified color. The points can be between pixels.
+pub fn draw_points_f<'a, P, C>(points: P, color: C) -> Result<(), String>
+where
+    P: Into<&'a [FPoint]>,
+    C: Into<Color>,
+{
+    with_engine_mut(|engine| {
+        let points = transform_points(transform::current(engine), points.into());
+        engine.canvas.set_draw_color(color);
+        // Safety: The renderer is valid for as long as the engine exists.
+        check(unsafe {
+            sys::SDL_RenderDrawPointsF(engine.canvas.raw(), points.as_ptr(), points.len() as c_int)
+        })
+    })
+}
+
+/// Draw a rectangle outline denoted by `rect` in the specified color. The rectangle can be
+/// between pixels.
+pub fn draw_rect_f<R, C>(rect: R, color: C) -> Result<(), String>
+where
+    R: Into<FRect>,
+    C: Into<Color>,
+{
+    draw_rects_f(&[rect.into()][..], color)
+}
+
+/// Draw a series of rectangle outlines in the specified color. The rectangles can be between
+/// pixels.
+pub fn draw_rects_f<'a, R, C>(rects: R, color: C) -> Result<(), String>
+where
+    R: Into<&'a [FRect]>,
+    C: Into<Color>,
+{
+    with_engine_mut(|engine| {
+        let t = transform::current(engine);
+        let rects = rects.into();
+        engine.canvas.set_draw_color(color);
+        if let Some(rects) = transform_rects(t, rects) {
+            // Safety: The renderer is valid for as long as the engine exists.
+            return check(unsafe {
+                sys::SDL_RenderDrawRectsF(engine.canvas.raw(), rects.as_ptr(), rects.len() as c_int)
+            });
+        }
+        for &rect in rects {
+            let mut points: Vec<_> = t
+                .corners(rect)
+                .iter()
+                .map(|&p| FPoint::from(p).raw())
+                .collect();
+            points.push(points[0]);
+            draw_lines_raw(&engine.canvas, &points)?;
+        }
+        Ok(())
+    })
+}
+
+/// Draw a filled rectangle denoted by `rect` in the specified color. The rectangle can be between
+/// pixels.
+pub fn fill_rect_f<R, C>(rect: R, color: C) -> Result<(), String>
+where
+    R: Into<FRect>,
+    C: Into<Color>,
+{
+    fill_rects_f(&[rect.into()][..], color)
+}
+
+/// Draw a series of filled rectangles in the specified color. The rectangles can be between
+/// pixels.
+pub fn fill_rects_f<'a, R, C>(rects: R, color: C) -> Result<(), String>
+where
+    R: Into<&'a [FRect]>,
+    C: Into<Color>,
+{
+    with_engine_mut(|engine| {
+        let t = transform::current(engine);
+        let rects = rects.into();
+        engine.canvas.set_draw_color(color);
+        if let Some(rects) = transform_rects(t, rects) {
+            // Safety: The renderer is valid for as long as the engine exists.
+            return check(unsafe {
+                sys::SDL_RenderFillRectsF(engine.canvas.raw(), rects.as_ptr(), rects.len() as c_int)
+            });
+        }
+        let spans: Vec<Rect> = rects
+            .iter()
+            .flat_map(|&rect| transform::spans(&t.corners(rect)))
+            .collect();
+        if spans.is_empty() {
+            return Ok(());
+        }
+        engine.canvas.fill_rects(&spans)
+    })
+}
+
+fn transform_rects(t: Transform, rects: &[FRect]) -> Option<Vec<sys::SDL_FRect>> {
+    rects
+        .iter()
+        .map(|&rect| t.frect(rect).map(FRect::raw))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+
+    /// Floating point rectangles are drawn where integer ones would be, and are transformed
+    /// without rounding first.
+    #[test]
+    fn test_fill_rect_f() {
+        test_util::check_frame(
+            16,
+            16,
+            || {
+                crate::clear(Color::BLACK);
+                fill_rect_f((2.0, 2.0, 4.0, 4.0), Color::GREEN)?;
+                crate::push_transform(Transform::scaling(2.0, 2.0));
+                fill_rect_f((4.5, 4.5, 1.5, 1.5), Color::RED)
+            },
+            |shot| {
+                assert_eq!(shot.pixel(2, 2), Some(Color::GREEN));
+                assert_eq!(shot.pixel(5, 5), Some(Color::GREEN));
+                assert_eq!(shot.pixel(6, 6), Some(Color::BLACK));
+                assert_eq!(shot.pixel(9, 9), Some(Color::RED));
+                assert_eq!(shot.pixel(11, 11), Some(Color::RED));
+                assert_eq!(shot.pixel(12, 12), Some(Color::BLACK));
+            },
+        );
+    }
+
+    /// Lines pass through every point, in order.
+    #[test]
+    fn test_draw_lines_f() {
+        test_util::check_frame(
+            8,
+            8,
+            || {
+                crate::clear(Color::BLACK);
+                let points = [
+                    FPoint::new(1.0, 1.0),
+                    FPoint::new(6.0, 1.0),
+                    FPoint::new(6.0, 6.0),
+                ];
+                draw_lines_f(&points[..], Color::RED)
+            },
+            |shot| {
+                assert_eq!(shot.pixel(3, 1), Some(Color::RED));
+                assert_eq!(shot.pixel(6, 4), Some(Color::RED));
+                assert_eq!(shot.pixel(3, 4), Some(Color::BLACK));
+            },
+        );
+    }
+
+    /// Points are drawn after being transformed.
+    #[test]
+    fn test_draw_points_f() {
+        test_util::check_frame(
+            8,
+            8,
+            || {
+                crate::clear(Color::BLACK);
+                crate::push_transform(Transform::translation(2.0, 3.0));
+                let points = [FPoint::new(1.0, 1.0), FPoint::new(4.0, 2.0)];
+                draw_points_f(&points[..], Color::GREEN)
+            },
+            |shot| {
+                assert_eq!(shot.pixel(3, 4), Some(Color::GREEN));
+                assert_eq!(shot.pixel(6, 5), Some(Color::GREEN));
+                assert_eq!(shot.pixel(1, 1), Some(Color::BLACK));
+            },
+        );
+    }
+}
@@ -1,12 +1,66 @@
+use std::{cmp::Ordering, collections::VecDeque, thread, time::Duration};
+
 use sdl2::TimerSubsystem;
 
+use crate::{with_engine, with_engine_mut};
+
+/// Time (in seconds) before the end of a frame at which the frame limiter stops sleeping, and
+/// busy-waits instead. Sleeping isn't precise enough to hit the target frame time exactly.
+const SPIN_TIME: f64 = 0.002;
+
+/// Get the frame rate the engine is limited to, if any.
+pub fn target_fps() -> Option<u32> {
+    with_engine(|e| e.target_fps)
+}
+
+/// Set or clear the frame rate the engine is limited to. When set, the engine waits between frames
+/// so as not to run faster than `fps` frames per second. This is independent of vsync, and saves
+/// power when vsync is disabled. A target of 0 is the same as `None`.
+pub fn set_target_fps<F: Into<Option<u32>>>(fps: F) {
+    let fps = fps.into().filter(|&fps| fps > 0);
+    with_engine_mut(|e| e.target_fps = fps)
+}
+
+/// Get statistics about the timing of recent frames.
+pub fn frame_stats() -> FrameStats {
+    with_engine(|e| e.fps.stats())
+}
+
+/// Number of recent frames over which rolling statistics are calculated.
+const WINDOW_FRAMES: usize = 120;
+
+/// Statistics about the timing of recent frames, as returned by [`frame_stats`].
+///
+/// All times are in seconds. Apart from the totals, statistics are calculated over a rolling
+/// window of the last 120 frames.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FrameStats {
+    /// Average frames per second.
+    pub fps: f64,
+    /// Average frame time.
+    pub frame_time_avg: f64,
+    /// Shortest frame time.
+    pub frame_time_min: f64,
+    /// Longest frame time.
+    pub frame_time_max: f64,
+    /// 95th percentile frame time. 95% of frames took this long or less.
+    pub frame_time_p95: f64,
+    /// 99th percentile frame time. 99% of frames took this long or less.
+    pub frame_time_p99: f64,
+    /// Number of frames since the engine started.
+    pub total_frames: u64,
+    /// Time since the engine started.
+    pub total_time: f64,
+}
+
 pub struct FpsCounter {
     timer: TimerSubsystem,
     last: u64,
     now: u64,
-    time_acc: f64,
-    fps_acc: f64,
-    fps_count: u64,
+    frame_times: VecDeque<f64>,
+    total_frames: u64,
+    total_time: f64,
+    last_report: f64,
 }
 
 impl FpsCounter {
@@ -16,43 +70,128 @@ impl FpsCounter {
             last: 0,
             timer,
             now,
-            time_acc: 0.0,
-            fps_acc: 0.0,
-            fps_count: 0,
+            frame_times: VecDeque::with_capacity(WINDOW_FRAMES),
+            total_frames: 0,
+            total_time: 0.0,
+            last_report: 0.0,
         }
     }
 
-    pub fn update(&mut self, calc_fps: bool) -> f64 {
+    pub fn update(&mut self) -> f64 {
         self.last = self.now;
         self.now = self.timer.performance_counter();
-        // Note: I have no idea whether this actually works, so if anyone would like to confirm
-        // or deny this, please do
         let elapsed_time =
             (self.now - self.last) as f64 / self.timer.performance_frequency() as f64;
-        if calc_fps {
-            self.time_acc += elapsed_time;
-            self.fps_acc += elapsed_time.recip();
-            self.fps_count += 1;
+
+        if self.frame_times.len() == WINDOW_FRAMES {
+            self.frame_times.pop_front();
         }
+        self.frame_times.push_back(elapsed_time);
+        self.total_frames += 1;
+        self.total_time += elapsed_time;
         elapsed_time
     }
 
-    /// Only valid if [`update`](Self::update) is called with `calc_fps` set to true.
-    #[inline]
-    pub fn time_acc(&self) -> f64 {
-        self.time_acc
+    /// Wait until `1 / fps` seconds have passed since the last call to [`update`](Self::update).
+    pub fn limit(&self, fps: u32) {
+        let freq = self.timer.performance_frequency();
+        let target = self.now + freq / fps as u64;
+        loop {
+            let now = self.timer.performance_counter();
+            if now >= target {
+                break;
+            }
+            let remaining = (target - now) as f64 / freq as f64;
+            if remaining > SPIN_TIME {
+                thread::sleep(Duration::from_secs_f64(remaining - SPIN_TIME));
+            } else {
+                std::hint::spin_loop();
+            }
+        }
     }
 
-    /// Only valid if [`update`](Self::update) is called with `calc_fps` set to true.
-    #[inline]
-    pub fn fps(&self) -> f64 {
-        self.fps_acc / self.fps_count as f64
+    /// Returns true at most once per `interval` seconds of running time. Used to periodically
+    /// report the frame rate.
+    pub fn report_due(&mut self, interval: f64) -> bool {
+        if self.total_time - self.last_report >= interval {
+            self.last_report = self.total_time;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn stats(&self) -> FrameStats {
+        let mut stats = window_stats(self.frame_times.iter().copied());
+        stats.total_frames = self.total_frames;
+        stats.total_time = self.total_time;
+        stats
+    }
+}
+
+/// Calculate the rolling statistics for a window of frame times.
+fn window_stats(frame_times: impl Iterator<Item = f64>) -> FrameStats {
+    let mut sorted: Vec<f64> = frame_times.collect();
+    if sorted.is_empty() {
+        return FrameStats::default();
+    }
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+
+    let sum: f64 = sorted.iter().sum();
+    FrameStats {
+        fps: if sum > 0.0 {
+            sorted.len() as f64 / sum
+        } else {
+            0.0
+        },
+        frame_time_avg: sum / sorted.len() as f64,
+        frame_time_min: sorted[0],
+        frame_time_max: sorted[sorted.len() - 1],
+        frame_time_p95: percentile(&sorted, 0.95),
+        frame_time_p99: percentile(&sorted, 0.99),
+        ..FrameStats::default()
+    }
+}
+
+/// The nearest-rank percentile `p` (from 0.0 to 1.0) of a sorted, non-empty slice.
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    let rank = (p * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::{test_util, Builder};
+
+    /// Rolling statistics are calculated over the window of frame times.
+    #[test]
+    fn test_window_stats() {
+        let stats = window_stats((1..=100).map(|ms| ms as f64 / 1000.0));
+        assert!((stats.fps - 1000.0 / 50.5).abs() < 1e-9);
+        assert!((stats.frame_time_avg - 0.0505).abs() < 1e-9);
+        assert_eq!(stats.frame_time_min, 0.001);
+        assert_eq!(stats.frame_time_max, 0.1);
+        assert_eq!(stats.frame_time_p95, 0.095);
+        assert_eq!(stats.frame_time_p99, 0.099);
+        assert_eq!(window_stats(std::iter::empty()), FrameStats::default());
     }
 
-    /// Only valid if [`update`](Self::update) is called with `calc_fps` set to true.
-    pub fn reset_average(&mut self) {
-        self.time_acc -= 1.0;
-        self.fps_acc = 0.0;
-        self.fps_count = 0;
+    /// The frame limiter stops frames from running faster than the target frame rate.
+    #[test]
+    fn test_target_fps() {
+        test_util::run(|| {
+            let start = Instant::now();
+            let mut app = |_| Ok(true);
+            Builder::new("Test", 16, 16)
+                .headless(true)
+                .target_fps(100)
+                .max_frames(6)
+                .start(&mut app)
+                .unwrap();
+            assert!(start.elapsed() >= Duration::from_millis(50));
+        });
     }
 }
@@ -0,0 +1,209 @@
+use sdl2::{
+    pixels::PixelFormatEnum,
+    render::{BlendMode, Texture as SdlTexture},
+};
+
+use crate::{engine::Engine, with_engine_mut, Color};
+
+/// The format of the framebuffer's pixels.
+const FORMAT: PixelFormatEnum = PixelFormatEnum::ARGB8888;
+
+/// A CPU-side image which can be written to directly, then drawn to the canvas all at once with
+/// [`draw_framebuffer`]. This is much faster than calling [`draw_point`][crate::draw_point] for
+/// every pixel, so is suitable for per-pixel effects and software rendering.
+///
+/// Pixels are stored in rows from top to bottom as `0xAARRGGBB` values.
+pub struct Framebuffer<'a> {
+    pixels: &'a mut [u32],
+    width: u32,
+    height: u32,
+}
+
+impl Framebuffer<'_> {
+    /// Get the width of the framebuffer in pixels.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Get the height of the framebuffer in pixels.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Get every pixel of the framebuffer, in rows from top to bottom. The pixel at (*x*, *y*) is
+    /// at index `y * width + x`.
+    pub fn pixels(&mut self) -> &mut [u32] {
+        self.pixels
+    }
+
+    /// Get the color of the pixel at (`x`, `y`), or `None` if it is outside the framebuffer.
+    pub fn pixel(&self, x: u32, y: u32) -> Option<Color> {
+        let i = self.index(x, y)?;
+        let [a, r, g, b] = self.pixels[i].to_be_bytes();
+        Some(Color::RGBA(r, g, b, a))
+    }
+
+    /// Set the color of the pixel at (`x`, `y`). Pixels outside the framebuffer are ignored.
+    pub fn set_pixel<C: Into<Color>>(&mut self, x: u32, y: u32, color: C) {
+        if let Some(i) = self.index(x, y) {
+            self.pixels[i] = to_argb(color.into());
+        }
+    }
+
+    /// Set every pixel to the specified color.
+    pub fn clear<C: Into<Color>>(&mut self, color: C) {
+        let color = to_argb(color.into());
+        self.pixels.iter_mut().for_each(|p| *p = color);
+    }
+
+    fn index(&self, x: u32, y: u32) -> Option<usize> {
+        if x < self.width && y < self.height {
+            Some(y as usize * self.width as usize + x as usize)
+        } else {
+            None
+        }
+    }
+}
+
+/// Convert a color to an `0xAARRGGBB` pixel.
+fn to_argb(color: Color) -> u32 {
+    let (r, g, b, a) = color.rgba();
+    u32::from_be_bytes([a, r, g, b])
+}
+
+/// The engine's framebuffer, and the streaming texture it is uploaded to.
+pub(crate) struct FramebufferState {
+    pixels: Vec<u32>,
+    width: u32,
+    height: u32,
+    texture: SdlTexture,
+}
+
+impl FramebufferState {
+    fn new(engine: &Engine, width: u32, height: u32) -> Result<Self, String> {
+        let mut texture = engine
+            .texture_creator
+            .create_texture_streaming(FORMAT, width.max(1), height.max(1))
+            .map_err(|e| e.to_string())?;
+        texture.set_blend_mode(BlendMode::Blend);
+        Ok(Self {
+            pixels: vec![0; width as usize * height as usize],
+            width,
+            height,
+            texture,
+        })
+    }
+
+    /// Destroy the texture. This must be called if the framebuffer is replaced while the engine
+    /// is running; otherwise the texture is destroyed along with the engine.
+    fn destroy(self) {
+        // Safety: The texture was created by the engine's texture creator, which still exists.
+        unsafe { self.texture.destroy() }
+    }
+}
+
+/// Get the engine's framebuffer, creating it the size of the current viewport if it doesn't
+/// exist.
+fn framebuffer(engine: &mut Engine) -> Result<&mut FramebufferState, String> {
+    if engine.framebuffer.is_none() {
+        let viewport = engine.canvas.viewport();
+        engine.framebuffer = Some(FramebufferState::new(
+            engine,
+            viewport.width(),
+            viewport.height(),
+        )?);
+    }
+    Ok(engine.framebuffer.as_mut().unwrap())
+}
+
+/// Run a function with access to the [`Framebuffer`]. Changes aren't shown until
+/// [`draw_framebuffer`] is called.
+///
+/// The framebuffer is created the first time this is called, with the size of the current
+/// viewport in drawing coordinates, and keeps its contents between frames. Use
+/// [`resize_framebuffer`] to change its size.
+///
+/// # Panics
+/// Panics if the framebuffer's texture can't be created. The engine can't be used from within
+/// `f`.
+pub fn with_framebuffer<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut Framebuffer) -> R,
+{
+    with_engine_mut(|engine| {
+        let state = framebuffer(engine).expect("Failed to create the framebuffer");
+        f(&mut Framebuffer {
+            pixels: &mut state.pixels,
+            width: state.width,
+            height: state.height,
+        })
+    })
+}
+
+/// Replace the framebuffer with a new, transparent one of the given size.
+pub fn resize_framebuffer(width: u32, height: u32) -> Result<(), String> {
+    with_engine_mut(|engine| {
+        let state = FramebufferState::new(engine, width, height)?;
+        if let Some(old) = engine.framebuffer.replace(state) {
+            old.destroy();
+        }
+        Ok(())
+    })
+}
+
+/// Upload the [`Framebuffer`] and draw it stretched over the current viewport. Its transparent
+/// pixels are blended with what is already drawn.
+///
+/// Anything drawn before this is covered by the framebuffer, and anything drawn after it is drawn
+/// over it, so it can be used as a background, an overlay, or both.
+pub fn draw_framebuffer() -> Result<(), String> {
+    with_engine_mut(|engine| {
+        let FramebufferState {
+            pixels,
+            width,
+            texture,
+            ..
+        } = framebuffer(engine)?;
+        let width = (*width).max(1) as usize;
+        texture.with_lock(None, |buf, pitch| {
+            for (dst, src) in buf.chunks_mut(pitch).zip(pixels.chunks(width)) {
+                for (dst, src) in dst.chunks_exact_mut(4).zip(src) {
+                    dst.copy_from_slice(&src.to_ne_bytes());
+                }
+            }
+        })?;
+        let texture = &engine.framebuffer.as_ref().unwrap().texture;
+        engine.canvas.copy(texture, None, None)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+
+    /// Pixels written to the framebuffer are drawn to the canvas.
+    #[test]
+    fn test_draw_framebuffer() {
+        test_util::check_frame(
+            8,
+            4,
+            || {
+                crate::clear(Color::BLUE);
+                with_framebuffer(|fb| {
+                    assert_eq!((fb.width(), fb.height()), (8, 4));
+                    fb.set_pixel(1, 2, Color::RED);
+                    assert_eq!(fb.pixel(1, 2), Some(Color::RED));
+                    fb.pixels()[0] = 0xFF00FF00;
+                });
+                draw_framebuffer()
+            },
+            |shot| {
+                assert_eq!(shot.pixel(1, 2), Some(Color::RED));
+                assert_eq!(shot.pixel(0, 0), Some(Color::GREEN));
+                // Transparent pixels show what was already drawn
+                assert_eq!(shot.pixel(7, 3), Some(Color::BLUE));
+            },
+        );
+    }
+}
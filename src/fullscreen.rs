@@ -1,3 +1,5 @@
+use sdl2::video::FullscreenType;
+
 /// Represents the possible fullscreen states of a window.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Fullscreen {
@@ -18,3 +20,23 @@ impl From<bool> for Fullscreen {
         }
     }
 }
+
+impl From<FullscreenType> for Fullscreen {
+    fn from(val: FullscreenType) -> Self {
+        match val {
+            FullscreenType::Off => Self::Off,
+            FullscreenType::True => Self::On,
+            FullscreenType::Desktop => Self::Desktop,
+        }
+    }
+}
+
+impl From<Fullscreen> for FullscreenType {
+    fn from(val: Fullscreen) -> Self {
+        match val {
+            Fullscreen::Off => Self::Off,
+            Fullscreen::On => Self::True,
+            Fullscreen::Desktop => Self::Desktop,
+        }
+    }
+}
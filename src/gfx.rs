@@ -2,7 +2,7 @@ use std::convert::TryInto;
 
 use sdl2::gfx::primitives::DrawRenderer;
 
-use crate::{with_engine, with_engine_mut, Color, Point};
+use crate::{engine::Engine, transform, with_engine, with_engine_mut, Color, Point, Transform};
 
 const CHAR_X_BOUNDS: &str = "Character x coordinate out of bounds, must fit in an i16";
 const CHAR_Y_BOUNDS: &str = "Character y coordinate out of bounds, must fit in an i16";
@@ -19,11 +19,67 @@ const STRING_Y_BOUNDS: &str = "String y coordinate out of bounds, must fit in an
 const TRIANGLE_X_BOUNDS: &str = "Triangle x coordinate out of bounds, must fit in an i16";
 const TRIANGLE_Y_BOUNDS: &str = "Triangle y coordinate out of bounds, must fit in an i16";
 
-fn to_xy<P: Into<Point>>(p: P, x_bounds: &'static str, y_bounds: &'static str) -> (i16, i16) {
+/// Convert a point to the coordinates used by SDL_gfx, which must fit in an `i16`. Points can be
+/// out of range after they are transformed, even if they were in range before.
+fn to_xy<P: Into<Point>>(
+    p: P,
+    x_bounds: &'static str,
+    y_bounds: &'static str,
+) -> Result<(i16, i16), String> {
     let p = p.into();
-    let x = p.x().try_into().expect(x_bounds);
-    let y = p.y().try_into().expect(y_bounds);
-    (x, y)
+    let x = p.x().try_into().map_err(|_| x_bounds.to_string())?;
+    let y = p.y().try_into().map_err(|_| y_bounds.to_string())?;
+    Ok((x, y))
+}
+
+/// An ellipse after it has been transformed.
+enum Ellipse {
+    /// An ellipse which is still aligned with the axes, with its center and radii.
+    Aligned((i16, i16), (i16, i16)),
+    /// A rotated ellipse, approximated by a polygon with these vertices.
+    Polygon(Vec<i16>, Vec<i16>),
+}
+
+impl Ellipse {
+    fn new<P, R>(transform: Transform, center: P, radii: R) -> Result<Self, String>
+    where
+        P: Into<Point>,
+        R: Into<Point>,
+    {
+        let center = center.into();
+        let radii = radii.into();
+        if transform.is_axis_aligned() {
+            let (scale_x, scale_y) = transform.scale();
+            let rx = (radii.x() as f32 * scale_x).round() as i32;
+            let ry = (radii.y() as f32 * scale_y.abs()).round() as i32;
+            let center = transform.apply_point(center);
+            return Ok(Self::Aligned(
+                to_xy(center, ELLIPSE_X_BOUNDS, ELLIPSE_Y_BOUNDS)?,
+                to_xy((rx, ry), ELLIPSE_RX_BOUNDS, ELLIPSE_RY_BOUNDS)?,
+            ));
+        }
+
+        let (rx, ry) = (radii.x() as f32, radii.y() as f32);
+        // Enough vertices that the edges are a few pixels long
+        let (scale_x, scale_y) = transform.scale();
+        let size = (rx + ry) * scale_x.max(scale_y.abs());
+        let count = (size as usize).clamp(16, 256);
+        let vertices = (0..count)
+            .map(|i| {
+                let angle = i as f32 / count as f32 * std::f32::consts::TAU;
+                let x = center.x() as f32 + rx * angle.cos();
+                let y = center.y() as f32 + ry * angle.sin();
+                let (x, y) = transform.apply(x, y);
+                to_xy(
+                    (x.round() as i32, y.round() as i32),
+                    ELLIPSE_X_BOUNDS,
+                    ELLIPSE_Y_BOUNDS,
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let (xs, ys) = vertices.into_iter().unzip();
+        Ok(Self::Polygon(xs, ys))
+    }
 }
 
 /// Returns whether shapes are currently being anti-aliased when drawn.
@@ -33,13 +89,17 @@ pub fn anti_aliased() -> bool {
 
 /// Draw a single character at `pos` in the specified color. The built-in font (from SDL_gfx) is
 /// used.
+///
+/// Only the position is affected by the [camera][crate::set_camera] and
+/// [transform stack][crate::push_transform]; the text isn't scaled or rotated.
 pub fn draw_char<P, C>(pos: P, character: char, color: C) -> Result<(), String>
 where
     P: Into<Point>,
     C: Into<Color>,
 {
     with_engine_mut(|engine| {
-        let (x, y) = to_xy(pos, CHAR_X_BOUNDS, CHAR_Y_BOUNDS);
+        let pos = transform::current(engine).apply_point(pos.into());
+        let (x, y) = to_xy(pos, CHAR_X_BOUNDS, CHAR_Y_BOUNDS)?;
 
         engine.canvas.character(x, y, character, color.into())
     })
@@ -52,7 +112,12 @@ where
     C: Into<Color>,
 {
     with_engine_mut(|engine| {
-        let (x, y) = to_xy(center, CIRCLE_X_BOUNDS, CIRCLE_Y_BOUNDS);
+        let t = transform::current(engine);
+        if t != Transform::IDENTITY {
+            let ellipse = Ellipse::new(t, center, (radius as i32, radius as i32))?;
+            return draw_ellipse_shape(engine, ellipse, color.into());
+        }
+        let (x, y) = to_xy(center, CIRCLE_X_BOUNDS, CIRCLE_Y_BOUNDS)?;
 
         let func = if engine.anti_alias {
             DrawRenderer::aa_circle
@@ -72,16 +137,8 @@ where
     C: Into<Color>,
 {
     with_engine_mut(|engine| {
-        let (x, y) = to_xy(center, ELLIPSE_X_BOUNDS, ELLIPSE_Y_BOUNDS);
-        let (rx, ry) = to_xy(radii, ELLIPSE_RX_BOUNDS, ELLIPSE_RY_BOUNDS);
-
-        let func = if engine.anti_alias {
-            DrawRenderer::aa_ellipse
-        } else {
-            DrawRenderer::ellipse
-        };
-
-        func(&engine.canvas, x, y, rx, ry, color.into())
+        let ellipse = Ellipse::new(transform::current(engine), center, radii)?;
+        draw_ellipse_shape(engine, ellipse, color.into())
     })
 }
 
@@ -93,8 +150,9 @@ where
     C: Into<Color>,
 {
     with_engine_mut(|engine| {
-        let (start_x, start_y) = to_xy(start, LINE_X_BOUNDS, LINE_Y_BOUNDS);
-        let (end_x, end_y) = to_xy(end, LINE_X_BOUNDS, LINE_Y_BOUNDS);
+        let t = transform::current(engine);
+        let (start_x, start_y) = to_xy(t.apply_point(start.into()), LINE_X_BOUNDS, LINE_Y_BOUNDS)?;
+        let (end_x, end_y) = to_xy(t.apply_point(end.into()), LINE_X_BOUNDS, LINE_Y_BOUNDS)?;
 
         let func = if engine.anti_alias {
             DrawRenderer::aa_line
@@ -107,13 +165,17 @@ where
 }
 
 /// Draw a text string at `pos` in the specified color. The built-in font (from SDL_gfx) is used.
+///
+/// Only the position is affected by the [camera][crate::set_camera] and
+/// [transform stack][crate::push_transform]; the text isn't scaled or rotated.
 pub fn draw_string<P, C>(pos: P, string: &str, color: C) -> Result<(), String>
 where
     P: Into<Point>,
     C: Into<Color>,
 {
     with_engine_mut(|engine| {
-        let (x, y) = to_xy(pos, STRING_X_BOUNDS, STRING_Y_BOUNDS);
+        let pos = transform::current(engine).apply_point(pos.into());
+        let (x, y) = to_xy(pos, STRING_X_BOUNDS, STRING_Y_BOUNDS)?;
 
         engine.canvas.string(x, y, string, color.into())
     })
@@ -128,9 +190,22 @@ where
     C: Into<Color>,
 {
     with_engine_mut(|engine| {
-        let (ax, ay) = to_xy(a, TRIANGLE_X_BOUNDS, TRIANGLE_Y_BOUNDS);
-        let (bx, by) = to_xy(b, TRIANGLE_X_BOUNDS, TRIANGLE_Y_BOUNDS);
-        let (cx, cy) = to_xy(c, TRIANGLE_X_BOUNDS, TRIANGLE_Y_BOUNDS);
+        let t = transform::current(engine);
+        let (ax, ay) = to_xy(
+            t.apply_point(a.into()),
+            TRIANGLE_X_BOUNDS,
+            TRIANGLE_Y_BOUNDS,
+        )?;
+        let (bx, by) = to_xy(
+            t.apply_point(b.into()),
+            TRIANGLE_X_BOUNDS,
+            TRIANGLE_Y_BOUNDS,
+        )?;
+        let (cx, cy) = to_xy(
+            t.apply_point(c.into()),
+            TRIANGLE_X_BOUNDS,
+            TRIANGLE_Y_BOUNDS,
+        )?;
 
         let func = if engine.anti_alias {
             DrawRenderer::aa_trigon
@@ -149,7 +224,12 @@ where
     C: Into<Color>,
 {
     with_engine_mut(|engine| {
-        let (x, y) = to_xy(center, CIRCLE_X_BOUNDS, CIRCLE_Y_BOUNDS);
+        let t = transform::current(engine);
+        if t != Transform::IDENTITY {
+            let ellipse = Ellipse::new(t, center, (radius as i32, radius as i32))?;
+            return fill_ellipse_shape(engine, ellipse, color.into());
+        }
+        let (x, y) = to_xy(center, CIRCLE_X_BOUNDS, CIRCLE_Y_BOUNDS)?;
 
         engine.canvas.filled_circle(x, y, radius, color.into())
     })
@@ -163,10 +243,8 @@ where
     C: Into<Color>,
 {
     with_engine_mut(|engine| {
-        let (x, y) = to_xy(center, ELLIPSE_X_BOUNDS, ELLIPSE_Y_BOUNDS);
-        let (rx, ry) = to_xy(radii, ELLIPSE_RX_BOUNDS, ELLIPSE_RY_BOUNDS);
-
-        engine.canvas.filled_ellipse(x, y, rx, ry, color.into())
+        let ellipse = Ellipse::new(transform::current(engine), center, radii)?;
+        fill_ellipse_shape(engine, ellipse, color.into())
     })
 }
 
@@ -179,9 +257,22 @@ where
     C: Into<Color>,
 {
     with_engine_mut(|engine| {
-        let (ax, ay) = to_xy(a, TRIANGLE_X_BOUNDS, TRIANGLE_Y_BOUNDS);
-        let (bx, by) = to_xy(b, TRIANGLE_X_BOUNDS, TRIANGLE_Y_BOUNDS);
-        let (cx, cy) = to_xy(c, TRIANGLE_X_BOUNDS, TRIANGLE_Y_BOUNDS);
+        let t = transform::current(engine);
+        let (ax, ay) = to_xy(
+            t.apply_point(a.into()),
+            TRIANGLE_X_BOUNDS,
+            TRIANGLE_Y_BOUNDS,
+        )?;
+        let (bx, by) = to_xy(
+            t.apply_point(b.into()),
+            TRIANGLE_X_BOUNDS,
+            TRIANGLE_Y_BOUNDS,
+        )?;
+        let (cx, cy) = to_xy(
+            t.apply_point(c.into()),
+            TRIANGLE_X_BOUNDS,
+            TRIANGLE_Y_BOUNDS,
+        )?;
 
         engine
             .canvas
@@ -189,6 +280,23 @@ where
     })
 }
 
+fn draw_ellipse_shape(engine: &Engine, ellipse: Ellipse, color: Color) -> Result<(), String> {
+    let canvas = &engine.canvas;
+    match (ellipse, engine.anti_alias) {
+        (Ellipse::Aligned((x, y), (rx, ry)), true) => canvas.aa_ellipse(x, y, rx, ry, color),
+        (Ellipse::Aligned((x, y), (rx, ry)), false) => canvas.ellipse(x, y, rx, ry, color),
+        (Ellipse::Polygon(xs, ys), true) => canvas.aa_polygon(&xs, &ys, color),
+        (Ellipse::Polygon(xs, ys), false) => canvas.polygon(&xs, &ys, color),
+    }
+}
+
+fn fill_ellipse_shape(engine: &Engine, ellipse: Ellipse, color: Color) -> Result<(), String> {
+    match ellipse {
+        Ellipse::Aligned((x, y), (rx, ry)) => engine.canvas.filled_ellipse(x, y, rx, ry, color),
+        Ellipse::Polygon(xs, ys) => engine.canvas.filled_polygon(&xs, &ys, color),
+    }
+}
+
 /// Sets if shapes should be anti-aliased when drawn. This smooths the edges of shapes, but is more
 /// CPU intensive.
 pub fn set_anti_alias(anti_alias: bool) {
@@ -196,3 +304,90 @@ pub fn set_anti_alias(anti_alias: bool) {
         engine.anti_alias = anti_alias;
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{clear, test_util};
+
+    /// Filled triangles cover their interior, and nothing outside it.
+    #[test]
+    fn test_fill_triangle() {
+        test_util::check_frame(
+            16,
+            16,
+            || {
+                clear(Color::BLACK);
+                fill_triangle((0, 0), (15, 0), (0, 15), Color::RED)
+            },
+            |shot| {
+                assert_eq!(shot.pixel(2, 2), Some(Color::RED));
+                assert_eq!(shot.pixel(14, 14), Some(Color::BLACK));
+            },
+        );
+    }
+
+    /// Filled circles cover their center, but not the corners of their bounding box.
+    #[test]
+    fn test_fill_circle() {
+        test_util::check_frame(
+            16,
+            16,
+            || {
+                clear(Color::BLACK);
+                fill_circle((8, 8), 6, Color::GREEN)
+            },
+            |shot| {
+                assert_eq!(shot.pixel(8, 8), Some(Color::GREEN));
+                assert_eq!(shot.pixel(2, 2), Some(Color::BLACK));
+            },
+        );
+    }
+
+    /// Shapes transformed out of the range SDL_gfx supports return an error instead of panicking.
+    #[test]
+    fn test_transformed_out_of_range() {
+        test_util::check_frame(
+            16,
+            16,
+            || {
+                clear(Color::BLACK);
+                crate::push_transform(Transform::scaling(10000.0, 10000.0));
+                assert!(fill_circle((8, 8), 6, Color::GREEN).is_err());
+                assert!(draw_line((0, 0), (15, 6), Color::WHITE).is_err());
+                crate::push_transform(Transform::rotation(30.0));
+                assert!(draw_ellipse((8, 8), (4, 2), Color::RED).is_err());
+                Ok(())
+            },
+            |shot| assert_eq!(shot.pixel(8, 8), Some(Color::BLACK)),
+        );
+    }
+
+    /// Anti-aliased lines blend their edges, while aliased lines don't.
+    #[test]
+    fn test_anti_alias() {
+        fn partial_pixels(shot: &crate::Surface) -> usize {
+            let (width, height) = shot.size();
+            (0..height)
+                .flat_map(|y| (0..width).map(move |x| (x, y)))
+                .filter(|&(x, y)| {
+                    let (r, _, _, _) = shot.pixel(x, y).unwrap().rgba();
+                    r > 0 && r < 255
+                })
+                .count()
+        }
+
+        for &anti_alias in &[false, true] {
+            test_util::check_frame(
+                16,
+                16,
+                move || {
+                    clear(Color::BLACK);
+                    set_anti_alias(anti_alias);
+                    draw_line((0, 0), (15, 6), Color::WHITE)
+                },
+                move |shot| assert_eq!(partial_pixels(shot) > 0, anti_alias),
+            );
+        }
+    }
+}
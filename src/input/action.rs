@@ -0,0 +1,490 @@
+//! Types for mapping named actions to inputs.
+
+use fnv::FnvHashMap;
+
+use super::{
+    controller::AXES, Button, ControllerAxis, ControllerButton, Controllers, KeyboardState,
+    MouseButton, MouseState, Scancode,
+};
+
+/// How far an input has to be pushed for an action bound to it to be held.
+const HOLD_THRESHOLD: f32 = 0.5;
+
+/// Returns whether an input with the given value is pushed far enough to be held.
+fn is_held(value: f32) -> bool {
+    value.abs() >= HOLD_THRESHOLD
+}
+
+/// A single input that an action can be bound to.
+///
+/// Controller inputs are read from every connected controller.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Input {
+    /// A key on the keyboard.
+    Key(Scancode),
+    /// A mouse button.
+    Mouse(MouseButton),
+    /// A button on a game controller.
+    ControllerButton(ControllerButton),
+    /// An axis on a game controller. An action bound to this is held when the axis is pushed at
+    /// least halfway in either direction.
+    ControllerAxis(ControllerAxis),
+}
+
+impl From<Scancode> for Input {
+    fn from(key: Scancode) -> Self {
+        Self::Key(key)
+    }
+}
+
+impl From<MouseButton> for Input {
+    fn from(button: MouseButton) -> Self {
+        Self::Mouse(button)
+    }
+}
+
+impl From<ControllerButton> for Input {
+    fn from(button: ControllerButton) -> Self {
+        Self::ControllerButton(button)
+    }
+}
+
+impl From<ControllerAxis> for Input {
+    fn from(axis: ControllerAxis) -> Self {
+        Self::ControllerAxis(axis)
+    }
+}
+
+/// A modifier key which has to be held for a [`Binding`] to be active. Either the left or right
+/// key can be held.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Modifier {
+    Ctrl,
+    Shift,
+    Alt,
+    /// The Windows, Command or Super key.
+    Gui,
+}
+
+impl Modifier {
+    /// Every modifier.
+    const ALL: [Modifier; 4] = [Self::Ctrl, Self::Shift, Self::Alt, Self::Gui];
+
+    /// The left and right keys for this modifier.
+    fn keys(self) -> [Scancode; 2] {
+        match self {
+            Self::Ctrl => [Scancode::LCtrl, Scancode::RCtrl],
+            Self::Shift => [Scancode::LShift, Scancode::RShift],
+            Self::Alt => [Scancode::LAlt, Scancode::RAlt],
+            Self::Gui => [Scancode::LGui, Scancode::RGui],
+        }
+    }
+}
+
+/// An input bound to an action, along with any modifiers that have to be held with it.
+///
+/// For example, `Binding::new(Scancode::S).with(Modifier::Ctrl)` is active while Ctrl+S is held.
+/// Extra modifiers can be held as well, unless another binding to the same input requires them:
+/// while Ctrl+S is held, a binding to S is only active if nothing is bound to Ctrl+S.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Binding {
+    pub input: Input,
+    pub modifiers: Vec<Modifier>,
+    /// The input's value is multiplied by this to get its contribution to the action's value.
+    pub scale: f32,
+}
+
+impl Binding {
+    /// Create a new binding to `input`, with no modifiers and a scale of 1.0.
+    pub fn new<I: Into<Input>>(input: I) -> Self {
+        Self {
+            input: input.into(),
+            modifiers: Vec::new(),
+            scale: 1.0,
+        }
+    }
+
+    /// Require a modifier to be held for this binding to be active.
+    pub fn with(mut self, modifier: Modifier) -> Self {
+        if !self.modifiers.contains(&modifier) {
+            self.modifiers.push(modifier);
+        }
+        self
+    }
+
+    /// Set the scale the input's value is multiplied by. For example, binding the left arrow key
+    /// with a scale of -1.0 and the right arrow key with a scale of 1.0 makes a horizontal axis.
+    pub fn scale(mut self, scale: f32) -> Self {
+        self.scale = scale;
+        self
+    }
+}
+
+impl<I: Into<Input>> From<I> for Binding {
+    fn from(input: I) -> Self {
+        Self::new(input)
+    }
+}
+
+/// The state of an action, and what it is bound to.
+struct ActionState {
+    bindings: Vec<Binding>,
+    button: Button,
+    value: f32,
+}
+
+/// The devices an action's inputs are read from.
+pub(crate) struct Devices<'a> {
+    pub keyboard: &'a KeyboardState,
+    pub mouse: &'a MouseState,
+    pub controllers: &'a Controllers,
+}
+
+impl Devices<'_> {
+    /// Get the value of an input, from 0.0 to 1.0 for buttons and -1.0 to 1.0 for axes.
+    fn value(&self, input: Input) -> f32 {
+        let held = |held: bool| if held { 1.0 } else { 0.0 };
+        let controllers = || self.controllers.connected.values();
+        match input {
+            Input::Key(key) => held(self.keyboard[key].held),
+            Input::Mouse(button) => held(self.mouse.buttons[button].held),
+            Input::ControllerButton(button) => held(controllers().any(|c| c.buttons[button].held)),
+            // Use whichever controller's axis is pushed furthest
+            Input::ControllerAxis(axis) => controllers()
+                .map(|c| c.axis(axis, self.controllers.deadzone))
+                .fold(0.0, |a: f32, b| if b.abs() > a.abs() { b } else { a }),
+        }
+    }
+
+    /// Find the binding for an input that was just used, along with any modifiers held with it.
+    ///
+    /// Modifier keys are bound by themselves if they are released without anything else being
+    /// pressed. Controller axes are bound once they are pushed at least halfway.
+    pub fn capture(&self) -> Option<Binding> {
+        let modifier_keys: Vec<Scancode> = Modifier::ALL.iter().flat_map(|m| m.keys()).collect();
+
+        let key = self
+            .keyboard
+            .pressed()
+            .find(|key| !modifier_keys.contains(key))
+            .map(|&key| Input::Key(key));
+        let mouse = || {
+            self.mouse
+                .buttons
+                .pressed()
+                .next()
+                .map(|&button| Input::Mouse(button))
+        };
+        if let Some(input) = key.or_else(mouse) {
+            let mut binding = Binding::new(input);
+            for &modifier in Modifier::ALL.iter() {
+                if self.modifiers_held(&[modifier]) {
+                    binding = binding.with(modifier);
+                }
+            }
+            return Some(binding);
+        }
+
+        let modifier_released = modifier_keys
+            .iter()
+            .find(|&&key| self.keyboard[key].released);
+        let no_modifiers_held = modifier_keys.iter().all(|&key| !self.keyboard[key].held);
+        if let (Some(&key), true) = (modifier_released, no_modifiers_held) {
+            return Some(Binding::new(key));
+        }
+
+        self.controllers.connected.values().find_map(|c| {
+            let button = c
+                .buttons
+                .pressed()
+                .next()
+                .map(|&button| Input::ControllerButton(button));
+            let deadzone = self.controllers.deadzone;
+            let axis = || {
+                AXES.iter()
+                    .find(|&&axis| is_held(c.axis(axis, deadzone)))
+                    .map(|&axis| Input::ControllerAxis(axis))
+            };
+            button.or_else(axis).map(Binding::new)
+        })
+    }
+
+    /// Returns whether every modifier is held.
+    fn modifiers_held(&self, modifiers: &[Modifier]) -> bool {
+        modifiers
+            .iter()
+            .all(|m| m.keys().iter().any(|&key| self.keyboard[key].held))
+    }
+}
+
+/// Every named action, and their states.
+#[derive(Default)]
+pub(crate) struct Actions(FnvHashMap<String, ActionState>);
+
+impl Actions {
+    /// Add a binding to an action, creating the action if it doesn't exist.
+    pub fn bind(&mut self, name: &str, binding: Binding) {
+        let action = self.get_or_insert(name);
+        if !action.bindings.contains(&binding) {
+            action.bindings.push(binding);
+        }
+    }
+
+    /// Remove an action and all of its bindings.
+    pub fn unbind(&mut self, name: &str) {
+        self.0.remove(name);
+    }
+
+    /// Replace the bindings of an action, creating the action if it doesn't exist.
+    pub fn set(&mut self, name: &str, bindings: Vec<Binding>) {
+        let action = self.get_or_insert(name);
+        action.bindings.clear();
+        for binding in bindings {
+            if !action.bindings.contains(&binding) {
+                action.bindings.push(binding);
+            }
+        }
+    }
+
+    fn get_or_insert(&mut self, name: &str) -> &mut ActionState {
+        self.0
+            .entry(name.to_string())
+            .or_insert_with(|| ActionState {
+                bindings: Vec::new(),
+                button: Button::default(),
+                value: 0.0,
+            })
+    }
+
+    /// Iterate over every action and its bindings, sorted by name.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &[Binding])> {
+        let mut actions: Vec<_> = self
+            .0
+            .iter()
+            .map(|(name, a)| (name.as_str(), a.bindings.as_slice()))
+            .collect();
+        actions.sort_by_key(|&(name, _)| name);
+        actions.into_iter()
+    }
+
+    /// Get the names of every action with a binding to the same input and modifiers as
+    /// `binding`, sorted by name. The scale isn't compared.
+    pub fn conflicts(&self, binding: &Binding) -> Vec<String> {
+        let same = |b: &Binding| {
+            b.input == binding.input
+                && b.modifiers.len() == binding.modifiers.len()
+                && b.modifiers.iter().all(|m| binding.modifiers.contains(m))
+        };
+        self.iter()
+            .filter(|(_, bindings)| bindings.iter().any(same))
+            .map(|(name, _)| name.to_string())
+            .collect()
+    }
+
+    /// Get the bindings of an action.
+    pub fn bindings(&self, name: &str) -> &[Binding] {
+        self.0.get(name).map_or(&[], |a| &a.bindings)
+    }
+
+    /// Get the state of an action as a button. Unknown actions are released.
+    pub fn button(&self, name: &str) -> Button {
+        self.0.get(name).map_or_else(Button::default, |a| a.button)
+    }
+
+    /// Get the value of an action. Unknown actions have a value of 0.0.
+    pub fn value(&self, name: &str) -> f32 {
+        self.0.get(name).map_or(0.0, |a| a.value)
+    }
+
+    /// Update the state of every action from the state of the input devices. This is called
+    /// internally by the engine every frame, after the devices have been updated.
+    ///
+    /// Of the bindings to the same input whose modifiers are held, only the most specific are
+    /// active, so holding Ctrl+S doesn't also trigger an action bound to S.
+    pub fn update(&mut self, devices: Devices) {
+        // The inputs and modifiers of every binding whose modifiers are held
+        let matching: Vec<(Input, Vec<Modifier>)> = self
+            .0
+            .values()
+            .flat_map(|a| &a.bindings)
+            .filter(|b| devices.modifiers_held(&b.modifiers))
+            .map(|b| (b.input, b.modifiers.clone()))
+            .collect();
+        let active = |b: &Binding| {
+            let more_specific = |(input, modifiers): &(Input, Vec<Modifier>)| {
+                *input == b.input
+                    && modifiers.len() > b.modifiers.len()
+                    && b.modifiers.iter().all(|m| modifiers.contains(m))
+            };
+            devices.modifiers_held(&b.modifiers) && !matching.iter().any(more_specific)
+        };
+
+        for action in self.0.values_mut() {
+            let mut held = false;
+            let mut value = 0.0;
+            for binding in action.bindings.iter().filter(|b| active(b)) {
+                let input = devices.value(binding.input);
+                held |= is_held(input);
+                value += input * binding.scale;
+            }
+            action.button.update(held);
+            action.value = value.clamp(-1.0, 1.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sdl2::mouse::MouseState as SdlMouseState;
+
+    use super::*;
+
+    /// Input devices where the keys held changed from `before` to `after` this frame, and the
+    /// `mouse` buttons were just pressed.
+    struct State {
+        keyboard: KeyboardState,
+        mouse: MouseState,
+        controllers: Controllers,
+    }
+
+    impl State {
+        fn new(before: &[Scancode], after: &[Scancode], mouse: &[MouseButton]) -> Self {
+            let keys = |held: &[Scancode]| {
+                let held = held.to_vec();
+                Modifier::ALL
+                    .iter()
+                    .flat_map(|m| m.keys())
+                    .chain(vec![Scancode::S, Scancode::Space])
+                    .map(move |key| (key, held.contains(&key)))
+            };
+            let mut keyboard = KeyboardState::new(keys(before));
+            keyboard.update(keys(after));
+            let mut state = MouseState::new(SdlMouseState::from_sdl_state(0));
+            let buttons = [MouseButton::Left, MouseButton::Right];
+            state.update(buttons.iter().map(|&b| (b, mouse.contains(&b))), 0, 0);
+            Self {
+                keyboard,
+                mouse: state,
+                controllers: Controllers::new(Err("No controllers in tests".to_string())),
+            }
+        }
+
+        fn devices(&self) -> Devices<'_> {
+            Devices {
+                keyboard: &self.keyboard,
+                mouse: &self.mouse,
+                controllers: &self.controllers,
+            }
+        }
+    }
+
+    /// The input that was just pressed is captured with the modifiers held with it.
+    #[test]
+    fn test_capture() {
+        let state = State::new(&[Scancode::LCtrl], &[Scancode::LCtrl, Scancode::S], &[]);
+        assert_eq!(
+            state.devices().capture(),
+            Some(Binding::new(Scancode::S).with(Modifier::Ctrl))
+        );
+
+        let state = State::new(&[], &[Scancode::RShift], &[MouseButton::Right]);
+        assert_eq!(
+            state.devices().capture(),
+            Some(Binding::new(MouseButton::Right).with(Modifier::Shift))
+        );
+
+        // A modifier on its own is only captured once it is released
+        let state = State::new(&[], &[Scancode::LAlt], &[]);
+        assert_eq!(state.devices().capture(), None);
+        let state = State::new(&[Scancode::LAlt], &[], &[]);
+        assert_eq!(
+            state.devices().capture(),
+            Some(Binding::new(Scancode::LAlt))
+        );
+
+        let state = State::new(&[Scancode::Space], &[Scancode::Space], &[]);
+        assert_eq!(state.devices().capture(), None);
+    }
+
+    /// A binding is inactive while a more specific binding to the same input is held.
+    #[test]
+    fn test_modifier_chords() {
+        let mut actions = Actions::default();
+        actions.bind("save", Binding::new(Scancode::S).with(Modifier::Ctrl));
+        actions.bind("down", Binding::new(Scancode::S));
+        actions.bind("jump", Binding::new(Scancode::Space));
+
+        let state = State::new(&[], &[Scancode::LCtrl, Scancode::S, Scancode::Space], &[]);
+        actions.update(state.devices());
+        assert!(actions.button("save").pressed);
+        assert!(!actions.button("down").held);
+        // Bindings without a more specific alternative still work with modifiers held
+        assert!(actions.button("jump").pressed);
+
+        let state = State::new(&[], &[Scancode::S], &[]);
+        actions.update(state.devices());
+        assert!(actions.button("save").released);
+        assert!(actions.button("down").pressed);
+    }
+
+    /// Values are scaled, summed and clamped, while buttons are held whatever their scale.
+    #[test]
+    fn test_value() {
+        let mut actions = Actions::default();
+        actions.set(
+            "move_x",
+            vec![
+                Binding::new(Scancode::S).scale(-1.0),
+                Binding::new(Scancode::Space),
+            ],
+        );
+        actions.bind("boost", Binding::new(Scancode::Space).scale(2.0));
+        actions.bind("creep", Binding::new(Scancode::Space).scale(0.25));
+
+        let state = State::new(&[], &[Scancode::S], &[]);
+        actions.update(state.devices());
+        assert_eq!(actions.value("move_x"), -1.0);
+
+        let state = State::new(&[], &[Scancode::S, Scancode::Space], &[]);
+        actions.update(state.devices());
+        assert_eq!(actions.value("move_x"), 0.0);
+        assert!(actions.button("move_x").held);
+        assert_eq!(actions.value("boost"), 1.0);
+        assert_eq!(actions.value("creep"), 0.25);
+        assert!(actions.button("creep").held);
+        assert_eq!(actions.value("unknown"), 0.0);
+    }
+
+    /// Inputs are held once they are pushed halfway in either direction.
+    #[test]
+    fn test_hold_threshold() {
+        assert!(!is_held(0.0));
+        assert!(!is_held(0.49));
+        assert!(is_held(0.5));
+        assert!(is_held(-0.75));
+        assert!(!is_held(-0.25));
+    }
+
+    /// Conflicts have the same input and modifiers, in any order, whatever their scale.
+    #[test]
+    fn test_conflicts() {
+        let save = Binding::new(Scancode::S)
+            .with(Modifier::Ctrl)
+            .with(Modifier::Shift);
+        let mut actions = Actions::default();
+        actions.bind("save as", save.clone());
+        actions.bind("down", Binding::new(Scancode::S));
+        actions.bind(
+            "export",
+            Binding::new(Scancode::S)
+                .with(Modifier::Shift)
+                .with(Modifier::Ctrl)
+                .scale(0.5),
+        );
+
+        assert_eq!(actions.conflicts(&save), vec!["export", "save as"]);
+        assert_eq!(actions.conflicts(&Binding::new(Scancode::S)), vec!["down"]);
+        let ctrl_s = Binding::new(Scancode::S).with(Modifier::Ctrl);
+        assert!(actions.conflicts(&ctrl_s).is_empty());
+    }
+}
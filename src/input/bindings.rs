@@ -0,0 +1,531 @@
+//! Stable names for inputs and bindings, and a file format for saving them.
+//!
+//! Bindings files are TOML, limited to a subset which [`write`] always produces and [`parse`]
+//! always accepts. See [`save_bindings`][crate::save_bindings] for the details.
+
+use std::{fmt, str::FromStr};
+
+use super::{Binding, ControllerAxis, ControllerButton, Input, Modifier, MouseButton, Scancode};
+
+/// The prefix of mouse button names.
+const MOUSE: &str = "Mouse ";
+/// The prefix of controller button and axis names.
+const PAD: &str = "Pad ";
+/// The prefix of keys SDL has no name for, which are named by their scancode number instead.
+const SCANCODE: &str = "Scancode ";
+/// Separates a binding from its scale.
+const SCALE: &str = " * ";
+
+impl fmt::Display for Modifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Ctrl => "Ctrl",
+            Self::Shift => "Shift",
+            Self::Alt => "Alt",
+            Self::Gui => "Gui",
+        })
+    }
+}
+
+impl FromStr for Modifier {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "ctrl" => Ok(Self::Ctrl),
+            "shift" => Ok(Self::Shift),
+            "alt" => Ok(Self::Alt),
+            "gui" => Ok(Self::Gui),
+            _ => Err(format!("Unknown modifier \"{}\"", s)),
+        }
+    }
+}
+
+/// Mouse buttons and their names, without the prefix.
+const MOUSE_BUTTONS: [(MouseButton, &str); 5] = [
+    (MouseButton::Left, "Left"),
+    (MouseButton::Middle, "Middle"),
+    (MouseButton::Right, "Right"),
+    (MouseButton::X1, "X1"),
+    (MouseButton::X2, "X2"),
+];
+
+/// Inputs are named as follows:
+///
+/// -   Keys use SDL's name for the scancode, such as `Space` or `Left Ctrl`. Keys SDL has no name
+///     for are `Scancode` followed by the scancode's number, such as `Scancode 100`.
+/// -   Mouse buttons are `Mouse Left`, `Mouse Middle`, `Mouse Right`, `Mouse X1` and `Mouse X2`.
+/// -   Controller buttons and axes are `Pad` followed by the name SDL uses in controller
+///     mappings, such as `Pad a` or `Pad leftx`.
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Key(key) => match key.name() {
+                "" => write!(f, "{}{}", SCANCODE, key as i32),
+                name => f.write_str(name),
+            },
+            Self::Mouse(button) => {
+                let name = MOUSE_BUTTONS
+                    .iter()
+                    .find(|&&(b, _)| b == button)
+                    .map_or("Unknown", |&(_, name)| name);
+                write!(f, "{}{}", MOUSE, name)
+            }
+            Self::ControllerButton(button) => write!(f, "{}{}", PAD, button.string()),
+            Self::ControllerAxis(axis) => write!(f, "{}{}", PAD, axis.string()),
+        }
+    }
+}
+
+impl FromStr for Input {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let unknown = || format!("Unknown input \"{}\"", s);
+        if let Some(name) = strip_prefix_ignore_case(s, MOUSE) {
+            MOUSE_BUTTONS
+                .iter()
+                .find(|(_, n)| n.eq_ignore_ascii_case(name))
+                .map(|&(button, _)| Self::Mouse(button))
+                .ok_or_else(unknown)
+        } else if let Some(name) = strip_prefix_ignore_case(s, PAD) {
+            let name = name.to_ascii_lowercase();
+            ControllerButton::from_string(&name)
+                .map(Self::ControllerButton)
+                .or_else(|| ControllerAxis::from_string(&name).map(Self::ControllerAxis))
+                .ok_or_else(unknown)
+        } else if let Some(number) = strip_prefix_ignore_case(s, SCANCODE) {
+            number
+                .parse()
+                .ok()
+                .and_then(Scancode::from_i32)
+                .map(Self::Key)
+                .ok_or_else(unknown)
+        } else {
+            Scancode::from_name(s).map(Self::Key).ok_or_else(unknown)
+        }
+    }
+}
+
+/// Bindings are named after their input, preceded by any modifiers separated by `+` and followed
+/// by ` * ` and the scale if it isn't 1, such as `Ctrl+Shift+S` or `Left * -1`.
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for modifier in &self.modifiers {
+            write!(f, "{}+", modifier)?;
+        }
+        write!(f, "{}", self.input)?;
+        if self.scale != 1.0 {
+            write!(f, "{}{}", SCALE, self.scale)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Binding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rest = s.trim();
+        let mut scale = 1.0;
+        // Some key names contain "*", so only treat it as a scale if it parses as one
+        if let Some((input, s)) = rest.rsplit_once(SCALE) {
+            if let Ok(s) = s.trim().parse() {
+                scale = s;
+                rest = input.trim_end();
+            }
+        }
+
+        // Some key names contain "+", so stop at the first thing that isn't a modifier
+        let mut modifiers = Vec::new();
+        while let Some((modifier, input)) = rest.split_once('+') {
+            match modifier.trim().parse() {
+                Ok(modifier) if !input.is_empty() => {
+                    modifiers.push(modifier);
+                    rest = input.trim_start();
+                }
+                _ => break,
+            }
+        }
+
+        let mut binding = Binding::new(rest.parse::<Input>()?).scale(scale);
+        for modifier in modifiers {
+            binding = binding.with(modifier);
+        }
+        Ok(binding)
+    }
+}
+
+fn strip_prefix_ignore_case<'a>(s: &'a str, prefix: &str) -> Option<&'a str> {
+    match s.get(..prefix.len()) {
+        Some(start) if start.eq_ignore_ascii_case(prefix) => Some(&s[prefix.len()..]),
+        _ => None,
+    }
+}
+
+/// Write actions and their bindings in the bindings file format.
+pub(crate) fn write<'a, I>(actions: I) -> String
+where
+    I: IntoIterator<Item = (&'a str, &'a [Binding])>,
+{
+    let mut out = String::new();
+    for (name, bindings) in actions {
+        let bindings: Vec<String> = bindings.iter().map(|b| quote(&b.to_string())).collect();
+        let name = if is_bare_key(name) {
+            name.to_string()
+        } else {
+            quote(name)
+        };
+        out.push_str(&format!("{} = [{}]\n", name, bindings.join(", ")));
+    }
+    out
+}
+
+/// Parse actions and their bindings from the bindings file format.
+pub(crate) fn parse(text: &str) -> Result<Vec<(String, Vec<Binding>)>, String> {
+    let mut parser = Parser {
+        rest: text,
+        line: 1,
+    };
+    let mut actions: Vec<(String, Vec<Binding>)> = Vec::new();
+    loop {
+        parser.skip(true);
+        if parser.rest.is_empty() {
+            return Ok(actions);
+        }
+        let line = parser.line;
+        let (name, bindings) = parser
+            .action()
+            .map_err(|e| format!("Line {}: {}", parser.line, e))?;
+        if actions.iter().any(|(n, _)| *n == name) {
+            return Err(format!(
+                "Line {}: Action \"{}\" is listed more than once",
+                line, name
+            ));
+        }
+        actions.push((name, bindings));
+    }
+}
+
+/// Returns whether a key can be written in TOML without quotes.
+fn is_bare_key(key: &str) -> bool {
+    !key.is_empty() && key.chars().all(is_bare_key_char)
+}
+
+fn is_bare_key_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '-'
+}
+
+/// Reads the subset of TOML used for bindings files.
+struct Parser<'a> {
+    rest: &'a str,
+    /// The line `rest` starts on, counting from 1.
+    line: usize,
+}
+
+impl Parser<'_> {
+    /// Skip spaces, tabs and comments, and line breaks if `newlines` is true.
+    fn skip(&mut self, newlines: bool) {
+        loop {
+            let rest = self.rest.trim_start_matches([' ', '\t']);
+            let rest = match rest.strip_prefix('#') {
+                Some(comment) => comment.trim_start_matches(|c| c != '\n'),
+                None => rest,
+            };
+            self.rest = rest;
+            match self.newline() {
+                true if newlines => {}
+                _ => return,
+            }
+        }
+    }
+
+    /// Skip a line break, returning whether there was one.
+    fn newline(&mut self) -> bool {
+        match self
+            .rest
+            .strip_prefix("\r\n")
+            .or_else(|| self.rest.strip_prefix('\n'))
+        {
+            Some(rest) => {
+                self.rest = rest;
+                self.line += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Skip `c`, returning whether it was next.
+    fn eat(&mut self, c: char) -> bool {
+        match self.rest.strip_prefix(c) {
+            Some(rest) => {
+                self.rest = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Parse a line of the form `name = ["binding", ...]`. The list can span several lines.
+    fn action(&mut self) -> Result<(String, Vec<Binding>), String> {
+        let name = self.key()?;
+        self.skip(false);
+        if self.rest.starts_with('.') {
+            return Err("Dotted keys aren't supported".to_string());
+        }
+        if !self.eat('=') {
+            return Err("Expected \"=\" after the action name".to_string());
+        }
+        self.skip(false);
+        if !self.eat('[') {
+            return Err("Expected a list of bindings".to_string());
+        }
+
+        let mut bindings = Vec::new();
+        loop {
+            self.skip(true);
+            if self.eat(']') {
+                break;
+            }
+            bindings.push(self.string()?.parse()?);
+            self.skip(true);
+            if !self.eat(',') {
+                self.skip(true);
+                if !self.eat(']') {
+                    return Err("Expected \",\" or \"]\" after a binding".to_string());
+                }
+                break;
+            }
+        }
+
+        self.skip(false);
+        if !self.rest.is_empty() && !self.newline() {
+            return Err(format!(
+                "Unexpected \"{}\" after the list",
+                self.first_line()
+            ));
+        }
+        Ok((name, bindings))
+    }
+
+    /// Parse a bare or quoted key.
+    fn key(&mut self) -> Result<String, String> {
+        if self.rest.starts_with('[') {
+            return Err("Tables aren't supported".to_string());
+        }
+        if self.rest.starts_with('"') || self.rest.starts_with('\'') {
+            return self.string();
+        }
+        let end = self
+            .rest
+            .find(|c| !is_bare_key_char(c))
+            .unwrap_or(self.rest.len());
+        if end == 0 {
+            return Err(format!(
+                "Expected an action name at \"{}\"",
+                self.first_line()
+            ));
+        }
+        let (key, rest) = self.rest.split_at(end);
+        self.rest = rest;
+        Ok(key.to_string())
+    }
+
+    /// Parse a single line basic (double quoted) or literal (single quoted) string.
+    fn string(&mut self) -> Result<String, String> {
+        if self.rest.starts_with("\"\"\"") || self.rest.starts_with("\'\'\'") {
+            return Err("Multi-line strings aren't supported".to_string());
+        }
+        let literal = match self.rest.chars().next() {
+            Some('"') => false,
+            Some('\'') => true,
+            _ => {
+                return Err(format!(
+                    "Expected a quoted string at \"{}\"",
+                    self.first_line()
+                ))
+            }
+        };
+
+        let mut chars = self.rest.char_indices().skip(1);
+        let mut out = String::new();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' if !literal => {
+                    self.rest = &self.rest[i + 1..];
+                    return Ok(out);
+                }
+                '\'' if literal => {
+                    self.rest = &self.rest[i + 1..];
+                    return Ok(out);
+                }
+                '\\' if !literal => out.push(unescape(&mut chars)?),
+                '\n' | '\r' => break,
+                c if c.is_control() && c != '\t' => {
+                    return Err("Control characters must be escaped".to_string())
+                }
+                c => out.push(c),
+            }
+        }
+        Err("Unterminated string".to_string())
+    }
+
+    /// Get the rest of the current line, for error messages.
+    fn first_line(&self) -> &str {
+        self.rest.lines().next().unwrap_or("").trim_end()
+    }
+}
+
+/// Parse the escape sequence following a backslash in a basic string.
+fn unescape(chars: &mut impl Iterator<Item = (usize, char)>) -> Result<char, String> {
+    let invalid = || "Invalid escape sequence".to_string();
+    let digits = match chars.next().ok_or_else(invalid)?.1 {
+        'b' => return Ok('\u{8}'),
+        't' => return Ok('\t'),
+        'n' => return Ok('\n'),
+        'f' => return Ok('\u{c}'),
+        'r' => return Ok('\r'),
+        '"' => return Ok('"'),
+        '\\' => return Ok('\\'),
+        'u' => 4,
+        'U' => 8,
+        _ => return Err(invalid()),
+    };
+    let hex: String = chars.take(digits).map(|(_, c)| c).collect();
+    if hex.len() != digits {
+        return Err(invalid());
+    }
+    u32::from_str_radix(&hex, 16)
+        .ok()
+        .and_then(char::from_u32)
+        .ok_or_else(invalid)
+}
+
+/// Quote a string as a TOML basic string, escaping quotes, backslashes and control characters.
+fn quote(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04X}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Bindings survive being written and parsed again.
+    #[test]
+    fn test_round_trip() {
+        let actions = vec![
+            ("jump".to_string(), vec![Binding::new(Scancode::Space)]),
+            (
+                "save as".to_string(),
+                vec![Binding::new(Scancode::S)
+                    .with(Modifier::Ctrl)
+                    .with(Modifier::Shift)],
+            ),
+            (
+                "move_x".to_string(),
+                vec![
+                    Binding::new(Scancode::Left).scale(-1.0),
+                    Binding::new(ControllerAxis::LeftX),
+                    Binding::new(Scancode::KpMultiply).scale(0.5),
+                ],
+            ),
+            (
+                "fire".to_string(),
+                vec![
+                    Binding::new(MouseButton::Left),
+                    Binding::new(ControllerButton::RightShoulder),
+                    Binding::new(Scancode::KpPlus).with(Modifier::Alt),
+                    Binding::new(Scancode::Backslash),
+                ],
+            ),
+            ("none".to_string(), vec![]),
+            (
+                "line\nbreak\t\"quoted\" \\ \u{7}".to_string(),
+                vec![Binding::new(Scancode::NonUsBackslash)],
+            ),
+        ];
+
+        let text = write(actions.iter().map(|(n, b)| (n.as_str(), b.as_slice())));
+        assert_eq!(text.lines().count(), actions.len());
+        assert!(text.contains("\"Scancode 100\""));
+        assert_eq!(parse(&text).unwrap(), actions);
+    }
+
+    /// Comments and whitespace are ignored, and errors give the line number.
+    #[test]
+    fn test_parse() {
+        let text = "# Controls\n\n  jump=[ \"space\" ,\"Pad a\",]  # Both\n";
+        assert_eq!(
+            parse(text).unwrap(),
+            vec![(
+                "jump".to_string(),
+                vec![
+                    Binding::new(Scancode::Space),
+                    Binding::new(ControllerButton::A)
+                ]
+            )]
+        );
+        assert_eq!(
+            parse("jump = [\"Space\"]\nfire = [\"Nope\"]").unwrap_err(),
+            "Line 2: Unknown input \"Nope\""
+        );
+    }
+
+    /// Arrays can span lines, and both kinds of strings are accepted.
+    #[test]
+    fn test_parse_toml() {
+        let text =
+            "'move x' = [\r\n  'Left * -1', # Left\n\n  \"\\u0052ight\",\n]\njump = []#None\n";
+        assert_eq!(
+            parse(text).unwrap(),
+            vec![
+                (
+                    "move x".to_string(),
+                    vec![
+                        Binding::new(Scancode::Left).scale(-1.0),
+                        Binding::new(Scancode::Right)
+                    ]
+                ),
+                ("jump".to_string(), vec![]),
+            ]
+        );
+    }
+
+    /// TOML outside the supported subset is rejected.
+    #[test]
+    fn test_parse_unsupported() {
+        let error = |text| parse(text).unwrap_err();
+        assert_eq!(error("[keys]\n"), "Line 1: Tables aren't supported");
+        assert_eq!(
+            error("player.jump = []"),
+            "Line 1: Dotted keys aren't supported"
+        );
+        assert_eq!(
+            error("jump = [\"Space\"] fire = []"),
+            "Line 1: Unexpected \"fire = []\" after the list"
+        );
+        assert_eq!(
+            error("jump = []\n\njump = []"),
+            "Line 3: Action \"jump\" is listed more than once"
+        );
+        assert_eq!(
+            error("jump = [\n\"Space\n\"]"),
+            "Line 2: Unterminated string"
+        );
+        assert_eq!(error("jump = 1"), "Line 1: Expected a list of bindings");
+    }
+}
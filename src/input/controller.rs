@@ -0,0 +1,187 @@
+//! Types related to the state of game controllers.
+
+use std::collections::BTreeMap;
+
+use sdl2::{controller::GameController, GameControllerSubsystem};
+
+use super::{ButtonState, ControllerAxis, ControllerButton};
+use crate::Event;
+
+/// Every button a game controller can have.
+const BUTTONS: [ControllerButton; 21] = [
+    ControllerButton::A,
+    ControllerButton::B,
+    ControllerButton::X,
+    ControllerButton::Y,
+    ControllerButton::Back,
+    ControllerButton::Guide,
+    ControllerButton::Start,
+    ControllerButton::LeftStick,
+    ControllerButton::RightStick,
+    ControllerButton::LeftShoulder,
+    ControllerButton::RightShoulder,
+    ControllerButton::DPadUp,
+    ControllerButton::DPadDown,
+    ControllerButton::DPadLeft,
+    ControllerButton::DPadRight,
+    ControllerButton::Misc1,
+    ControllerButton::Paddle1,
+    ControllerButton::Paddle2,
+    ControllerButton::Paddle3,
+    ControllerButton::Paddle4,
+    ControllerButton::Touchpad,
+];
+
+/// Every axis a game controller can have.
+pub(super) const AXES: [ControllerAxis; 6] = [
+    ControllerAxis::LeftX,
+    ControllerAxis::LeftY,
+    ControllerAxis::RightX,
+    ControllerAxis::RightY,
+    ControllerAxis::TriggerLeft,
+    ControllerAxis::TriggerRight,
+];
+
+/// The deadzone used until it is changed with
+/// [`set_controller_deadzone`][crate::set_controller_deadzone].
+const DEFAULT_DEADZONE: f32 = 0.15;
+
+/// The state of a single connected game controller.
+pub(crate) struct ControllerState {
+    pub controller: GameController,
+    /// The state of every button on the controller.
+    pub buttons: ButtonState<ControllerButton>,
+    /// The raw position of every axis on the controller, in the same order as [`AXES`].
+    axes: [i16; AXES.len()],
+}
+
+impl ControllerState {
+    fn new(controller: GameController) -> Self {
+        let buttons = ButtonState::new(BUTTONS.iter().map(|&b| (b, controller.button(b))));
+        let mut state = Self {
+            controller,
+            buttons,
+            axes: [0; AXES.len()],
+        };
+        state.read_axes();
+        state
+    }
+
+    fn update(&mut self) {
+        let controller = &self.controller;
+        self.buttons
+            .update(BUTTONS.iter().map(|&b| (b, controller.button(b))));
+        self.read_axes();
+    }
+
+    fn read_axes(&mut self) {
+        for (value, &axis) in self.axes.iter_mut().zip(AXES.iter()) {
+            *value = self.controller.axis(axis);
+        }
+    }
+
+    /// Get the position of an axis, from -1.0 to 1.0 for sticks and from 0.0 to 1.0 for
+    /// triggers, with a deadzone applied.
+    pub fn axis(&self, axis: ControllerAxis, deadzone: f32) -> f32 {
+        let i = AXES.iter().position(|&a| a == axis).unwrap_or_default();
+        apply_deadzone(self.axes[i] as f32 / i16::MAX as f32, deadzone)
+    }
+}
+
+/// All connected game controllers.
+pub(crate) struct Controllers {
+    /// SDL's game controller support, or why it couldn't be started.
+    pub subsystem: Result<GameControllerSubsystem, String>,
+    /// The connected controllers, by instance ID.
+    pub connected: BTreeMap<u32, ControllerState>,
+    /// Axis positions smaller than this are treated as 0.
+    pub deadzone: f32,
+}
+
+impl Controllers {
+    /// Open every game controller that is already connected. If `subsystem` is an error, no
+    /// controllers are ever connected.
+    pub fn new(subsystem: Result<GameControllerSubsystem, String>) -> Self {
+        let mut controllers = Self {
+            subsystem,
+            connected: BTreeMap::new(),
+            deadzone: DEFAULT_DEADZONE,
+        };
+        let count = controllers
+            .subsystem
+            .as_ref()
+            .map_or(0, |s| s.num_joysticks().unwrap_or(0));
+        for index in 0..count {
+            controllers.open(index);
+        }
+        controllers
+    }
+
+    /// Open the game controller with the given joystick index, if it is one. Controllers which
+    /// can't be opened are ignored.
+    fn open(&mut self, index: u32) {
+        let subsystem = match self.subsystem {
+            Ok(ref subsystem) if subsystem.is_game_controller(index) => subsystem,
+            _ => return,
+        };
+        if let Ok(controller) = subsystem.open(index) {
+            self.connected
+                .entry(controller.instance_id())
+                .or_insert_with(|| ControllerState::new(controller));
+        }
+    }
+
+    /// Handle controllers being connected or disconnected, then update the state of every
+    /// controller. This is called internally by the engine every frame.
+    pub fn update(&mut self, events: &[Event]) {
+        for event in events {
+            match *event {
+                Event::ControllerDeviceAdded { which, .. } => self.open(which),
+                Event::ControllerDeviceRemoved { which, .. } => {
+                    self.connected.remove(&which);
+                }
+                _ => {}
+            }
+        }
+        self.connected
+            .values_mut()
+            .for_each(ControllerState::update);
+    }
+}
+
+/// Set axis positions within `deadzone` of the center to 0, scaling the rest so the output still
+/// covers the full range.
+fn apply_deadzone(value: f32, deadzone: f32) -> f32 {
+    let value = value.clamp(-1.0, 1.0);
+    if value.abs() <= deadzone {
+        0.0
+    } else {
+        value.signum() * (value.abs() - deadzone) / (1.0 - deadzone)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Small values are ignored, and the remaining range is rescaled.
+    #[test]
+    fn test_apply_deadzone() {
+        assert_eq!(apply_deadzone(0.1, 0.25), 0.0);
+        assert_eq!(apply_deadzone(-0.25, 0.25), 0.0);
+        assert_eq!(apply_deadzone(0.625, 0.25), 0.5);
+        assert_eq!(apply_deadzone(-1.0, 0.25), -1.0);
+        assert_eq!(apply_deadzone(0.5, 0.0), 0.5);
+    }
+
+    /// Without SDL's controller support, no controllers are connected, even when one is reported.
+    #[test]
+    fn test_unsupported() {
+        let mut controllers = Controllers::new(Err("Not supported".to_string()));
+        controllers.update(&[Event::ControllerDeviceAdded {
+            timestamp: 0,
+            which: 0,
+        }]);
+        assert!(controllers.connected.is_empty());
+    }
+}
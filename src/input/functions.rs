@@ -1,23 +1,301 @@
-use super::{Button, MouseButton, Scancode};
-use crate::{Point, ENGINE, NOT_INIT};
+use std::{fs, path::Path};
+
+use super::{
+    bindings, Binding, Button, Composition, ControllerAxis, ControllerButton, MouseButton, Scancode,
+};
+use crate::{scaling::Mapping, with_engine, with_engine_mut, Point, Rect};
 
 pub fn key(key: Scancode) -> Button {
-    ENGINE.with(|e| {
-        let engine = e.get().expect(NOT_INIT).borrow();
-        engine.input.keyboard[key]
-    })
+    with_engine(|engine| engine.input.keyboard[key])
 }
 
 pub fn mouse_button(button: MouseButton) -> Button {
-    ENGINE.with(|e| {
-        let engine = e.get().expect(NOT_INIT).borrow();
-        engine.input.mouse.buttons[button]
-    })
+    with_engine(|engine| engine.input.mouse.buttons[button])
+}
+
+/// Get every key that was pressed this frame. This is useful for "press any key" screens and
+/// capturing keys to bind.
+pub fn keys_pressed() -> Vec<Scancode> {
+    with_engine(|engine| engine.input.keyboard.pressed().copied().collect())
+}
+
+/// Get every key that is held down.
+pub fn keys_held() -> Vec<Scancode> {
+    with_engine(|engine| engine.input.keyboard.held().copied().collect())
+}
+
+/// Get every key that was released this frame.
+pub fn keys_released() -> Vec<Scancode> {
+    with_engine(|engine| engine.input.keyboard.released().copied().collect())
+}
+
+/// Returns whether any key was pressed this frame.
+pub fn any_key_pressed() -> bool {
+    with_engine(|engine| engine.input.keyboard.pressed().next().is_some())
+}
+
+/// Get every mouse button that was pressed this frame.
+pub fn mouse_buttons_pressed() -> Vec<MouseButton> {
+    with_engine(|engine| engine.input.mouse.buttons.pressed().copied().collect())
 }
 
+/// Get the position of the mouse cursor in the coordinates used for drawing. See
+/// [`window_to_logical`][crate::window_to_logical].
 pub fn mouse_pos() -> Point {
-    ENGINE.with(|e| {
-        let engine = e.get().expect(NOT_INIT).borrow();
-        Point::new(engine.input.mouse.x, engine.input.mouse.y)
+    with_engine(|engine| {
+        let pos = Point::new(engine.input.mouse.x, engine.input.mouse.y);
+        Mapping::new(&engine.canvas).to_logical(pos)
+    })
+}
+
+/// Get the position of the mouse cursor in window coordinates, without any scaling applied.
+pub fn mouse_window_pos() -> Point {
+    with_engine(|engine| Point::new(engine.input.mouse.x, engine.input.mouse.y))
+}
+
+/// Get how far the mouse moved since the last frame, in window coordinates. This keeps working
+/// in [relative mouse mode][set_relative_mouse_mode], when the cursor doesn't move.
+pub fn mouse_delta() -> (i32, i32) {
+    with_engine(|engine| (engine.input.mouse.dx, engine.input.mouse.dy))
+}
+
+/// Get how far the mouse wheel was scrolled since the last frame, horizontally and vertically.
+/// Scrolling right and away from the user are positive, whichever way the platform reports them.
+///
+/// The values are whole numbers of scroll steps, as SDL reports them. Smooth scrolling devices
+/// such as touchpads only report a step once they have scrolled far enough.
+pub fn mouse_wheel() -> (f32, f32) {
+    with_engine(|engine| (engine.input.mouse.wheel_x, engine.input.mouse.wheel_y))
+}
+
+/// Returns whether relative mouse mode is enabled.
+pub fn relative_mouse_mode() -> bool {
+    with_engine(|engine| engine.sdl.mouse().relative_mouse_mode())
+}
+
+/// Enable or disable relative mouse mode. While it is enabled, the cursor is hidden and kept
+/// within the window, and only [`mouse_delta`] reports movement. This is useful for first person
+/// and camera drag controls.
+pub fn set_relative_mouse_mode(enabled: bool) {
+    with_engine(|engine| engine.sdl.mouse().set_relative_mouse_mode(enabled))
+}
+
+/// Returns whether the cursor is confined to the window.
+pub fn mouse_grab() -> bool {
+    with_engine(|engine| engine.canvas.window().grab())
+}
+
+/// Set whether the cursor is confined to the window.
+pub fn set_mouse_grab(grabbed: bool) {
+    with_engine_mut(|engine| engine.canvas.window_mut().set_grab(grabbed))
+}
+
+/// Check whether game controllers can be used. If SDL's game controller support fails to start,
+/// the engine runs without it, no controllers are ever connected, and this returns the reason.
+pub fn controller_support() -> Result<(), String> {
+    with_engine(|engine| match engine.input.controllers.subsystem {
+        Ok(_) => Ok(()),
+        Err(ref e) => Err(e.clone()),
+    })
+}
+
+/// Get the instance IDs of every connected game controller, which are used to identify them in
+/// the other controller functions. A controller that is disconnected and reconnected gets a new
+/// ID.
+pub fn controllers() -> Vec<u32> {
+    with_engine(|engine| engine.input.controllers.connected.keys().copied().collect())
+}
+
+/// Get the name of a connected game controller.
+pub fn controller_name(id: u32) -> Option<String> {
+    with_engine(|engine| {
+        let controller = engine.input.controllers.connected.get(&id)?;
+        Some(controller.controller.name())
+    })
+}
+
+/// Get the state of a button on a game controller. If the controller isn't connected, the button
+/// is released.
+pub fn controller_button(id: u32, button: ControllerButton) -> Button {
+    with_engine(|engine| {
+        engine
+            .input
+            .controllers
+            .connected
+            .get(&id)
+            .map_or_else(Button::default, |c| c.buttons[button])
+    })
+}
+
+/// Get the position of an axis on a game controller, from -1.0 to 1.0 for sticks (positive is
+/// right and down) and from 0.0 to 1.0 for triggers. Positions within the
+/// [deadzone][set_controller_deadzone] are 0.0. If the controller isn't connected, this is 0.0.
+pub fn controller_axis(id: u32, axis: ControllerAxis) -> f32 {
+    with_engine(|engine| {
+        let controllers = &engine.input.controllers;
+        controllers
+            .connected
+            .get(&id)
+            .map_or(0.0, |c| c.axis(axis, controllers.deadzone))
+    })
+}
+
+/// Get the deadzone applied to controller axes.
+pub fn controller_deadzone() -> f32 {
+    with_engine(|engine| engine.input.controllers.deadzone)
+}
+
+/// Set the deadzone applied to controller axes, from 0.0 to 1.0. Axis positions closer to the
+/// center than this are reported as 0.0, to hide the drift of worn sticks. The default is 0.15.
+pub fn set_controller_deadzone(deadzone: f32) {
+    with_engine_mut(|engine| engine.input.controllers.deadzone = deadzone.clamp(0.0, 1.0))
+}
+
+/// Make a game controller rumble for `duration_ms` milliseconds. `low` and `high` are the
+/// strengths of the low and high frequency motors, from 0 to `u16::MAX`. Starting a new rumble
+/// replaces the current one, and a strength of 0 stops it.
+pub fn controller_rumble(id: u32, low: u16, high: u16, duration_ms: u32) -> Result<(), String> {
+    with_engine_mut(|engine| {
+        let controller = engine
+            .input
+            .controllers
+            .connected
+            .get_mut(&id)
+            .ok_or_else(|| format!("No controller is connected with ID {}", id))?;
+        controller
+            .controller
+            .set_rumble(low, high, duration_ms)
+            .map_err(|e| e.to_string())
+    })
+}
+
+/// Bind a named action to an input, in addition to any inputs it is already bound to. The action
+/// is created if it doesn't exist, and its state is updated from the next frame onwards.
+pub fn bind_action<B: Into<Binding>>(name: &str, binding: B) {
+    with_engine_mut(|engine| engine.input.actions.bind(name, binding.into()))
+}
+
+/// Remove a named action, along with all of its bindings.
+pub fn unbind_action(name: &str) {
+    with_engine_mut(|engine| engine.input.actions.unbind(name))
+}
+
+/// Get the bindings of a named action.
+pub fn action_bindings(name: &str) -> Vec<Binding> {
+    with_engine(|engine| engine.input.actions.bindings(name).to_vec())
+}
+
+/// Get the state of a named action as a button. The action is held while any of its bindings are
+/// held, or for axes, pushed at least halfway. Actions that don't exist are released.
+pub fn action(name: &str) -> Button {
+    with_engine(|engine| engine.input.actions.button(name))
+}
+
+/// Get the value of a named action, from -1.0 to 1.0. This is the sum of the values of its
+/// bindings (1.0 for held buttons, or the position of axes) multiplied by their scales. Actions
+/// that don't exist have a value of 0.0.
+pub fn action_value(name: &str) -> f32 {
+    with_engine(|engine| engine.input.actions.value(name))
+}
+
+/// Get the names of every action that has a binding to the same input and modifiers as
+/// `binding`. This can be used to warn the user before rebinding a control.
+pub fn binding_conflicts(binding: &Binding) -> Vec<String> {
+    with_engine(|engine| engine.input.actions.conflicts(binding))
+}
+
+/// Get the binding for the first input that was used this frame, for rebinding controls in an
+/// options menu. Call this every frame while waiting for the user to choose an input.
+///
+/// Keys and mouse buttons are captured along with any modifiers held with them, such as Ctrl+S.
+/// A modifier key by itself is captured when it is released. Controller axes are captured once
+/// they are pushed at least halfway.
+pub fn capture_binding() -> Option<Binding> {
+    with_engine(|engine| engine.input.devices().capture())
+}
+
+/// Save every action and its bindings to a file, which can be edited by hand and loaded with
+/// [`load_bindings`]. See [`Binding`] for how the bindings are named.
+///
+/// The file is TOML, with a key for each action whose value is an array of binding names:
+///
+/// ```toml
+/// # Comments start with a hash
+/// jump = ["Space", "Pad a"]
+/// save = ['Ctrl+S']
+/// "move x" = [
+///     "Left * -1",
+///     "Right",  # Trailing commas are allowed
+/// ]
+/// ```
+///
+/// Only this subset of TOML can be loaded: top level keys, which are bare or quoted, with arrays
+/// of single line strings as values, and comments. Tables, dotted keys, multi-line strings and
+/// other kinds of values are rejected, as are actions listed more than once.
+pub fn save_bindings<P: AsRef<Path>>(path: P) -> Result<(), String> {
+    let text = with_engine(|engine| bindings::write(engine.input.actions.iter()));
+    fs::write(path, text).map_err(|e| e.to_string())
+}
+
+/// Load actions and their bindings from a file saved with [`save_bindings`]. Actions in the file
+/// replace any existing actions with the same names, while other actions are left as they are.
+/// If the file has any errors, no actions are changed.
+pub fn load_bindings<P: AsRef<Path>>(path: P) -> Result<(), String> {
+    let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let actions = bindings::parse(&text)?;
+    with_engine_mut(|engine| {
+        for (name, bindings) in actions {
+            engine.input.actions.set(&name, bindings);
+        }
+    });
+    Ok(())
+}
+
+/// Start accepting text input, which can then be read with [`text_input`]. On some platforms
+/// this shows an on-screen keyboard.
+///
+/// `rect` is the area where the text is being entered, in the coordinates used for drawing.
+/// Input methods use this to position their candidate list without covering the text.
+pub fn start_text_input<R: Into<Option<Rect>>>(rect: R) {
+    with_engine_mut(|engine| {
+        let text_input = engine.video.text_input();
+        if let Some(rect) = rect.into() {
+            let mapping = Mapping::new(&engine.canvas);
+            let top_left = mapping.to_window(rect.top_left());
+            let bottom_right = mapping.to_window(rect.bottom_right());
+            text_input.set_rect(
+                Rect::from_enclose_points(&[top_left, bottom_right], None).unwrap_or(rect),
+            );
+        }
+        text_input.start();
+    })
+}
+
+/// Stop accepting text input, discarding any text being composed.
+pub fn stop_text_input() {
+    with_engine_mut(|engine| {
+        engine.video.text_input().stop();
+        engine.input.text.composition = Composition::default();
+    })
+}
+
+/// Returns whether text input is being accepted.
+pub fn text_input_active() -> bool {
+    with_engine(|engine| engine.video.text_input().is_active())
+}
+
+/// Get the text that was entered this frame. This is empty if nothing was entered, or text input
+/// isn't [active][start_text_input].
+pub fn text_input() -> String {
+    with_engine(|engine| engine.input.text.input.clone())
+}
+
+/// Get the text being composed with an input method, if any. This should be drawn where the text
+/// is being entered until it is replaced by a composition or entered text, which is returned by
+/// [`text_input`].
+pub fn text_composition() -> Option<Composition> {
+    with_engine(|engine| {
+        let composition = &engine.input.text.composition;
+        Some(composition.clone()).filter(|c| !c.text.is_empty())
     })
 }
@@ -8,9 +8,38 @@ use state::ButtonState;
 mod mouse;
 pub(crate) use mouse::MouseState;
 
+mod controller;
+pub(crate) use controller::Controllers;
+
+mod action;
+use action::{Actions, Devices};
+pub use action::{Binding, Input, Modifier};
+pub(crate) mod bindings;
+
+mod text;
+pub use text::Composition;
+use text::TextState;
+
+mod record;
+pub(crate) use record::{Recording, Snapshot};
+
+mod synthetic;
+use synthetic::Synthetic;
+
+use std::mem;
+
+use fnv::FnvHashMap;
+use sdl2::{EventPump, GameControllerSubsystem};
+
+use crate::Event;
+
 pub(crate) mod functions;
 
-pub use sdl2::{keyboard::Scancode, mouse::MouseButton};
+pub use sdl2::{
+    controller::{Axis as ControllerAxis, Button as ControllerButton},
+    keyboard::Scancode,
+    mouse::MouseButton,
+};
 
 pub(crate) type KeyboardState = ButtonState<Scancode>;
 
@@ -18,6 +47,139 @@ pub(crate) type KeyboardState = ButtonState<Scancode>;
 pub(crate) struct InputState {
     /// State of every SDL2 supported key on the keyboard
     pub keyboard: KeyboardState,
-    /// State of every SDL2 supported mouse button, as well as the cursor's *x* and *y* coordinates
+    /// State of every SDL2 supported mouse button, the cursor's *x* and *y* coordinates, and the
+    /// motion and scrolling this frame
     pub mouse: MouseState,
+    /// State of every connected game controller
+    pub controllers: Controllers,
+    /// State of every named action, and the inputs they are bound to
+    pub actions: Actions,
+    /// Text entered this frame, and any text being composed
+    pub text: TextState,
+    /// Synthetic input that overrides the real state of the input devices
+    pub injected: InjectedInput,
+}
+
+impl InputState {
+    /// Create an initial state from the current state of the input devices.
+    pub fn new(events: &EventPump, controllers: Result<GameControllerSubsystem, String>) -> Self {
+        Self {
+            keyboard: KeyboardState::new(events.keyboard_state().scancodes()),
+            mouse: MouseState::new(events.mouse_state()),
+            controllers: Controllers::new(controllers),
+            actions: Actions::default(),
+            text: TextState::default(),
+            injected: InjectedInput::default(),
+        }
+    }
+
+    /// Get the input devices, to read the inputs of actions from.
+    pub fn devices(&self) -> Devices<'_> {
+        Devices {
+            keyboard: &self.keyboard,
+            mouse: &self.mouse,
+            controllers: &self.controllers,
+        }
+    }
+
+    /// Update the state from the current state of the input devices and the events that arrived
+    /// since the last frame, applying any injected input. This is called internally by the engine
+    /// every frame.
+    ///
+    /// Events are generated for changes caused by injected input, in the window with ID
+    /// `window_id`, and added to `frame_events`.
+    pub fn update(&mut self, events: &EventPump, frame_events: &mut Vec<Event>, window_id: u32) {
+        let injected = &self.injected;
+        let prev_pos = (self.mouse.x, self.mouse.y);
+
+        self.keyboard.update(
+            events
+                .keyboard_state()
+                .scancodes()
+                .map(|(key, state)| (key, injected.keys.get(&key).copied().unwrap_or(state))),
+        );
+
+        let mouse = events.mouse_state();
+        let (x, y) = injected.mouse_pos.unwrap_or((mouse.x(), mouse.y()));
+        self.mouse.update(
+            mouse.mouse_buttons().map(|(button, state)| {
+                let state = injected
+                    .mouse_buttons
+                    .get(&button)
+                    .copied()
+                    .unwrap_or(state);
+                (button, state)
+            }),
+            x,
+            y,
+        );
+        self.mouse.accumulate(frame_events);
+        if injected.mouse_pos.is_some() {
+            self.mouse.dx = x - prev_pos.0;
+            self.mouse.dy = y - prev_pos.1;
+        }
+        self.text.update(frame_events);
+        let text = mem::take(&mut self.injected.text);
+        self.text.input.push_str(&text);
+        self.update_derived(frame_events);
+
+        // Changes without an event from the device itself were caused by injected input
+        let mut synthetic = Synthetic::new(self, window_id);
+        synthetic.keys(|key| !frame_events.iter().any(|e| synthetic::reports_key(e, key)));
+        synthetic.mouse_buttons(|button| {
+            !frame_events
+                .iter()
+                .any(|e| synthetic::reports_mouse_button(e, button))
+        });
+        if self.injected.mouse_pos.is_some() {
+            frame_events.retain(|e| !matches!(e, Event::MouseMotion { .. }));
+            synthetic.mouse_motion();
+        }
+        synthetic.text(&text);
+        frame_events.extend(synthetic.finish());
+    }
+
+    /// Update the state from a recorded snapshot instead of the keyboard and mouse. Controllers
+    /// aren't recorded, so are still updated from the events that arrived since the last frame.
+    /// This is called internally by the engine every frame while replaying a recording.
+    ///
+    /// The keyboard and mouse events in `frame_events` are replaced with events generated from
+    /// the snapshot, in the window with ID `window_id`, so only the recorded input is seen.
+    pub fn replay(&mut self, snapshot: &Snapshot, frame_events: &mut Vec<Event>, window_id: u32) {
+        frame_events.retain(|e| !synthetic::from_keyboard_or_mouse(e));
+        snapshot.apply(self);
+        self.update_derived(frame_events);
+
+        let mut synthetic = Synthetic::new(self, window_id);
+        synthetic.keys(|_| true);
+        synthetic.mouse_buttons(|_| true);
+        synthetic.mouse_motion();
+        synthetic.mouse_wheel();
+        synthetic.text(&self.text.input);
+        frame_events.extend(synthetic.finish());
+    }
+
+    /// Update controllers and actions, after the keyboard and mouse have been updated.
+    fn update_derived(&mut self, frame_events: &[Event]) {
+        self.controllers.update(frame_events);
+        self.actions.update(Devices {
+            keyboard: &self.keyboard,
+            mouse: &self.mouse,
+            controllers: &self.controllers,
+        });
+    }
+}
+
+/// Synthetic input, which takes priority over the real state of the input devices until it is
+/// removed.
+#[derive(Default)]
+pub(crate) struct InjectedInput {
+    /// Keys that are held down (`true`) or released (`false`)
+    pub keys: FnvHashMap<Scancode, bool>,
+    /// Mouse buttons that are held down (`true`) or released (`false`)
+    pub mouse_buttons: FnvHashMap<MouseButton, bool>,
+    /// Position of the mouse cursor
+    pub mouse_pos: Option<(i32, i32)>,
+    /// Text entered on the next frame, which is then cleared
+    pub text: String,
 }
@@ -1,6 +1,8 @@
 //! Types related to the state of the mouse.
 
-use sdl2::mouse::MouseState as SdlMouseState;
+use sdl2::mouse::{MouseState as SdlMouseState, MouseWheelDirection};
+
+use crate::Event;
 
 use super::{ButtonState, MouseButton};
 
@@ -12,6 +14,17 @@ pub struct MouseState {
     pub x: i32,
     /// *Y* coordinate of the mouse cursor.
     pub y: i32,
+    /// How far the mouse moved horizontally this frame. Unlike the position, this keeps changing
+    /// in relative mouse mode.
+    pub dx: i32,
+    /// How far the mouse moved vertically this frame.
+    pub dy: i32,
+    /// How far the mouse wheel was scrolled horizontally this frame, in whole scroll steps.
+    /// Positive is to the right.
+    pub wheel_x: f32,
+    /// How far the mouse wheel was scrolled vertically this frame, in whole scroll steps.
+    /// Positive is away from the user.
+    pub wheel_y: f32,
 }
 
 impl MouseState {
@@ -21,14 +34,96 @@ impl MouseState {
             buttons: ButtonState::new(state.mouse_buttons()),
             x: state.x(),
             y: state.y(),
+            dx: 0,
+            dy: 0,
+            wheel_x: 0.0,
+            wheel_y: 0.0,
+        }
+    }
+
+    /// Update the existing state from the current state of the buttons and position of the
+    /// cursor. This is called internally by the engine on every frame.
+    pub(crate) fn update(
+        &mut self,
+        buttons: impl Iterator<Item = (MouseButton, bool)>,
+        x: i32,
+        y: i32,
+    ) {
+        self.buttons.update(buttons);
+        self.x = x;
+        self.y = y;
+    }
+
+    /// Add up the motion and scrolling from the events that arrived this frame, replacing the
+    /// totals from the previous frame. This is called internally by the engine on every frame.
+    pub(crate) fn accumulate(&mut self, events: &[Event]) {
+        let (mut dx, mut dy, mut wheel_x, mut wheel_y) = (0, 0, 0.0, 0.0);
+        for event in events {
+            match *event {
+                Event::MouseMotion { xrel, yrel, .. } => {
+                    dx += xrel;
+                    dy += yrel;
+                }
+                Event::MouseWheel {
+                    x, y, direction, ..
+                } => {
+                    // Some platforms report "natural" scrolling as flipped values
+                    let sign = match direction {
+                        MouseWheelDirection::Flipped => -1.0,
+                        _ => 1.0,
+                    };
+                    wheel_x += x as f32 * sign;
+                    wheel_y += y as f32 * sign;
+                }
+                _ => {}
+            }
         }
+        self.dx = dx;
+        self.dy = dy;
+        self.wheel_x = wheel_x;
+        self.wheel_y = wheel_y;
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Motion and scrolling are summed over the frame's events, and reset every frame.
+    #[test]
+    fn test_accumulate() {
+        let motion = |xrel, yrel| Event::MouseMotion {
+            timestamp: 0,
+            window_id: 0,
+            which: 0,
+            mousestate: SdlMouseState::from_sdl_state(0),
+            x: 0,
+            y: 0,
+            xrel,
+            yrel,
+        };
+        let wheel = |y, direction| Event::MouseWheel {
+            timestamp: 0,
+            window_id: 0,
+            which: 0,
+            x: 0,
+            y,
+            direction,
+        };
+
+        let mut mouse = MouseState::new(SdlMouseState::from_sdl_state(0));
+        mouse.accumulate(&[
+            motion(3, -1),
+            motion(2, 4),
+            wheel(1, MouseWheelDirection::Normal),
+            wheel(1, MouseWheelDirection::Flipped),
+            wheel(2, MouseWheelDirection::Flipped),
+        ]);
+        assert_eq!((mouse.dx, mouse.dy), (5, 3));
+        assert_eq!((mouse.wheel_x, mouse.wheel_y), (0.0, -2.0));
 
-    /// Update the existing state from the current `sdl2::mouse::MouseState`. This is called
-    /// internally by the engine on every frame.
-    pub(crate) fn update(&mut self, state: SdlMouseState) {
-        self.buttons.update(state.mouse_buttons());
-        self.x = state.x();
-        self.y = state.y();
+        mouse.accumulate(&[]);
+        assert_eq!((mouse.dx, mouse.dy), (0, 0));
+        assert_eq!((mouse.wheel_x, mouse.wheel_y), (0.0, 0.0));
     }
 }
@@ -0,0 +1,303 @@
+//! Recording the input state to a file, and replaying it.
+//!
+//! A recording starts with [`MAGIC`], followed by one snapshot per frame:
+//!
+//! | Field                          | Type                      |
+//! |--------------------------------|---------------------------|
+//! | Elapsed time                   | `f64`                     |
+//! | Number of keys held            | `u16`                     |
+//! | Scancode of each key held      | `i32`                     |
+//! | Mouse buttons held             | `u8`, one bit per button  |
+//! | Mouse *x*, *y*, *dx* and *dy*  | `i32`                     |
+//! | Mouse wheel *x* and *y*        | `f32`                     |
+//! | Length of text entered         | `u32`                     |
+//! | Text entered                   | UTF-8                     |
+//!
+//! All numbers are little-endian.
+
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, BufReader, BufWriter, ErrorKind, Read, Write},
+    path::Path,
+};
+
+use super::{InputState, MouseButton, Scancode};
+
+/// Identifies a file as an input recording, including the version of the format.
+const MAGIC: &[u8; 8] = b"SGEINP01";
+
+/// The mouse buttons that are recorded, in the order of their bits.
+const MOUSE_BUTTONS: [MouseButton; 5] = [
+    MouseButton::Left,
+    MouseButton::Middle,
+    MouseButton::Right,
+    MouseButton::X1,
+    MouseButton::X2,
+];
+
+/// The state of the keyboard and mouse during a single frame.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Snapshot {
+    pub elapsed_time: f64,
+    pub keys: Vec<Scancode>,
+    pub mouse_buttons: u8,
+    pub mouse_pos: (i32, i32),
+    pub mouse_delta: (i32, i32),
+    pub wheel: (f32, f32),
+    pub text: String,
+}
+
+impl Snapshot {
+    /// Take a snapshot of the input state.
+    pub fn capture(input: &InputState, elapsed_time: f64) -> Self {
+        let mouse = &input.mouse;
+        let mouse_buttons = MOUSE_BUTTONS
+            .iter()
+            .enumerate()
+            .filter(|&(_, &b)| mouse.buttons[b].held)
+            .fold(0, |mask, (i, _)| mask | 1 << i);
+        let mut keys: Vec<Scancode> = input.keyboard.held().copied().collect();
+        keys.sort_by_key(|&k| k as i32);
+        Self {
+            elapsed_time,
+            keys,
+            mouse_buttons,
+            mouse_pos: (mouse.x, mouse.y),
+            mouse_delta: (mouse.dx, mouse.dy),
+            wheel: (mouse.wheel_x, mouse.wheel_y),
+            text: input.text.input.clone(),
+        }
+    }
+
+    /// Replace the keyboard and mouse state with this snapshot.
+    pub fn apply(&self, input: &mut InputState) {
+        let released: Vec<Scancode> = input
+            .keyboard
+            .held()
+            .filter(|key| !self.keys.contains(key))
+            .copied()
+            .collect();
+        input.keyboard.update(
+            released
+                .into_iter()
+                .map(|key| (key, false))
+                .chain(self.keys.iter().map(|&key| (key, true))),
+        );
+
+        let mouse = &mut input.mouse;
+        mouse.update(
+            MOUSE_BUTTONS
+                .iter()
+                .enumerate()
+                .map(|(i, &b)| (b, self.mouse_buttons & 1 << i != 0)),
+            self.mouse_pos.0,
+            self.mouse_pos.1,
+        );
+        mouse.dx = self.mouse_delta.0;
+        mouse.dy = self.mouse_delta.1;
+        mouse.wheel_x = self.wheel.0;
+        mouse.wheel_y = self.wheel.1;
+
+        input.text.input.clone_from(&self.text);
+    }
+
+    /// Write this snapshot to the end of a recording.
+    pub fn write<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_all(&self.elapsed_time.to_le_bytes())?;
+        w.write_all(&(self.keys.len() as u16).to_le_bytes())?;
+        for &key in &self.keys {
+            w.write_all(&(key as i32).to_le_bytes())?;
+        }
+        w.write_all(&[self.mouse_buttons])?;
+        let (x, y) = self.mouse_pos;
+        let (dx, dy) = self.mouse_delta;
+        for n in &[x, y, dx, dy] {
+            w.write_all(&n.to_le_bytes())?;
+        }
+        w.write_all(&self.wheel.0.to_le_bytes())?;
+        w.write_all(&self.wheel.1.to_le_bytes())?;
+        w.write_all(&(self.text.len() as u32).to_le_bytes())?;
+        w.write_all(self.text.as_bytes())
+    }
+
+    /// Read a snapshot, returning `None` at the end of the recording.
+    fn read<R: Read>(r: &mut R) -> io::Result<Option<Self>> {
+        let mut elapsed_time = [0; 8];
+        let mut filled = 0;
+        while filled < elapsed_time.len() {
+            match r.read(&mut elapsed_time[filled..]) {
+                // The recording can only end between snapshots
+                Ok(0) if filled == 0 => return Ok(None),
+                Ok(0) => return Err(truncated()),
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        Self::read_rest(r, f64::from_le_bytes(elapsed_time))
+            .map(Some)
+            .map_err(|e| match e.kind() {
+                ErrorKind::UnexpectedEof => truncated(),
+                _ => e,
+            })
+    }
+
+    /// Read the rest of a snapshot, after its elapsed time.
+    fn read_rest<R: Read>(r: &mut R, elapsed_time: f64) -> io::Result<Self> {
+        let keys = (0..read_u16(r)?)
+            .map(|_| {
+                let key = read_i32(r)?;
+                Scancode::from_i32(key).ok_or_else(|| invalid(&format!("Invalid scancode {}", key)))
+            })
+            .collect::<io::Result<_>>()?;
+        let [mouse_buttons] = read_bytes(r)?;
+        let mouse_pos = (read_i32(r)?, read_i32(r)?);
+        let mouse_delta = (read_i32(r)?, read_i32(r)?);
+        let wheel = (read_f32(r)?, read_f32(r)?);
+        let mut text = vec![0; read_u32(r)? as usize];
+        r.read_exact(&mut text)?;
+
+        Ok(Self {
+            elapsed_time,
+            keys,
+            mouse_buttons,
+            mouse_pos,
+            mouse_delta,
+            wheel,
+            text: String::from_utf8(text).map_err(|_| invalid("Text isn't valid UTF-8"))?,
+        })
+    }
+}
+
+/// Reading or writing a recording of the input state.
+pub(crate) enum Recording {
+    /// Recording every frame to a file.
+    Record(BufWriter<File>),
+    /// Replaying the remaining frames of a recording.
+    Replay(VecDeque<Snapshot>),
+}
+
+impl Recording {
+    /// Create a new recording at `path`, overwriting any existing file.
+    pub fn record<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(MAGIC)?;
+        Ok(Self::Record(file))
+    }
+
+    /// Load a recording from `path` to replay.
+    pub fn replay<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        read(&mut BufReader::new(File::open(path)?)).map(Self::Replay)
+    }
+}
+
+/// Read every snapshot in a recording.
+fn read<R: Read>(r: &mut R) -> io::Result<VecDeque<Snapshot>> {
+    let mut magic = [0; MAGIC.len()];
+    r.read_exact(&mut magic)
+        .ok()
+        .filter(|_| &magic == MAGIC)
+        .ok_or_else(|| invalid("Not an input recording"))?;
+
+    let mut snapshots = VecDeque::new();
+    while let Some(snapshot) = Snapshot::read(r)? {
+        snapshots.push_back(snapshot);
+    }
+    Ok(snapshots)
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, msg)
+}
+
+fn truncated() -> io::Error {
+    invalid("Truncated recording")
+}
+
+fn read_bytes<R: Read, const N: usize>(r: &mut R) -> io::Result<[u8; N]> {
+    let mut buf = [0; N];
+    r.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+fn read_u16<R: Read>(r: &mut R) -> io::Result<u16> {
+    read_bytes(r).map(u16::from_le_bytes)
+}
+
+fn read_u32<R: Read>(r: &mut R) -> io::Result<u32> {
+    read_bytes(r).map(u32::from_le_bytes)
+}
+
+fn read_i32<R: Read>(r: &mut R) -> io::Result<i32> {
+    read_bytes(r).map(i32::from_le_bytes)
+}
+
+fn read_f32<R: Read>(r: &mut R) -> io::Result<f32> {
+    read_bytes(r).map(f32::from_le_bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Snapshots survive being written and read again.
+    #[test]
+    fn test_round_trip() {
+        let snapshots = vec![
+            Snapshot {
+                elapsed_time: 1.0 / 60.0,
+                keys: vec![Scancode::A, Scancode::LShift],
+                mouse_buttons: 0b101,
+                mouse_pos: (10, -3),
+                mouse_delta: (2, 0),
+                wheel: (0.0, -1.5),
+                text: "Aé".to_string(),
+            },
+            Snapshot {
+                elapsed_time: 0.02,
+                keys: vec![],
+                mouse_buttons: 0,
+                mouse_pos: (0, 0),
+                mouse_delta: (0, 0),
+                wheel: (0.0, 0.0),
+                text: String::new(),
+            },
+        ];
+
+        let mut buf = MAGIC.to_vec();
+        for snapshot in &snapshots {
+            snapshot.write(&mut buf).unwrap();
+        }
+        assert_eq!(read(&mut buf.as_slice()).unwrap(), snapshots);
+
+        assert!(read(&mut &b"garbage"[..]).is_err());
+    }
+
+    /// Recordings that end partway through a snapshot are errors.
+    #[test]
+    fn test_truncated() {
+        let snapshot = Snapshot {
+            elapsed_time: 0.5,
+            keys: vec![Scancode::Space],
+            mouse_buttons: 0,
+            mouse_pos: (1, 2),
+            mouse_delta: (0, 0),
+            wheel: (0.0, 0.0),
+            text: "a".to_string(),
+        };
+        let mut buf = MAGIC.to_vec();
+        snapshot.write(&mut buf).unwrap();
+        let len = buf.len();
+        snapshot.write(&mut buf).unwrap();
+
+        // Partway through the elapsed time, and through the rest of the snapshot
+        for end in &[len + 3, buf.len() - 3] {
+            let err = read(&mut &buf[..*end]).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidData);
+            assert_eq!(err.to_string(), "Truncated recording");
+        }
+        assert_eq!(read(&mut &buf[..len]).unwrap(), vec![snapshot]);
+    }
+}
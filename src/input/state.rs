@@ -30,9 +30,29 @@ where
         }
     }
 
-    /// Get the state of a specific button.
+    /// Get the state of a specific button. Buttons which have never been seen are released.
     pub fn get(&self, button: T) -> &Button {
-        self.0.get(&button).unwrap()
+        static RELEASED: Button = Button {
+            pressed: false,
+            released: false,
+            held: false,
+        };
+        self.0.get(&button).unwrap_or(&RELEASED)
+    }
+
+    /// Iterate over the buttons that were pressed this frame.
+    pub fn pressed(&self) -> impl Iterator<Item = &T> {
+        self.0.iter().filter(|(_, b)| b.pressed).map(|(t, _)| t)
+    }
+
+    /// Iterate over the buttons that are held down.
+    pub fn held(&self) -> impl Iterator<Item = &T> {
+        self.0.iter().filter(|(_, b)| b.held).map(|(t, _)| t)
+    }
+
+    /// Iterate over the buttons that were released this frame.
+    pub fn released(&self) -> impl Iterator<Item = &T> {
+        self.0.iter().filter(|(_, b)| b.released).map(|(t, _)| t)
     }
 }
 
@@ -46,3 +66,32 @@ where
         self.get(button)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Buttons that were never reported are released, rather than panicking.
+    #[test]
+    fn test_unknown_button() {
+        let state = ButtonState::new(vec![(1, true)].into_iter());
+        assert!(state[1].held);
+        assert_eq!(state[2], Button::default());
+    }
+
+    /// Buttons can be filtered by their state.
+    #[test]
+    fn test_iter_state() {
+        let mut state = ButtonState::new(vec![(1, true), (2, false), (3, true)].into_iter());
+        state.update(vec![(1, false), (2, true), (3, true), (4, true)].into_iter());
+
+        let sorted = |iter: &mut dyn Iterator<Item = &i32>| {
+            let mut v: Vec<i32> = iter.copied().collect();
+            v.sort_unstable();
+            v
+        };
+        assert_eq!(sorted(&mut state.pressed()), vec![2, 4]);
+        assert_eq!(sorted(&mut state.held()), vec![2, 3, 4]);
+        assert_eq!(sorted(&mut state.released()), vec![1]);
+    }
+}
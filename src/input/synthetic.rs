@@ -0,0 +1,217 @@
+//! Events for keyboard and mouse input that didn't come from the devices themselves, such as
+//! replayed input, so that applications handling events see it too.
+
+use sdl2::{
+    keyboard::{Keycode, Mod},
+    mouse::{MouseState as SdlMouseState, MouseWheelDirection},
+    sys,
+};
+
+use super::{InputState, MouseButton, Scancode};
+use crate::Event;
+
+/// The modifier keys, and the flags they set in key events.
+const MODIFIERS: [(Scancode, Mod); 8] = [
+    (Scancode::LShift, Mod::LSHIFTMOD),
+    (Scancode::RShift, Mod::RSHIFTMOD),
+    (Scancode::LCtrl, Mod::LCTRLMOD),
+    (Scancode::RCtrl, Mod::RCTRLMOD),
+    (Scancode::LAlt, Mod::LALTMOD),
+    (Scancode::RAlt, Mod::RALTMOD),
+    (Scancode::LGui, Mod::LGUIMOD),
+    (Scancode::RGui, Mod::RGUIMOD),
+];
+
+/// Returns whether an event comes from the keyboard or mouse, including text input.
+pub(crate) fn from_keyboard_or_mouse(event: &Event) -> bool {
+    matches!(
+        event,
+        Event::KeyDown { .. }
+            | Event::KeyUp { .. }
+            | Event::TextEditing { .. }
+            | Event::TextInput { .. }
+            | Event::MouseMotion { .. }
+            | Event::MouseButtonDown { .. }
+            | Event::MouseButtonUp { .. }
+            | Event::MouseWheel { .. }
+    )
+}
+
+/// Returns whether an event reports a key being pressed or released.
+pub(crate) fn reports_key(event: &Event, key: Scancode) -> bool {
+    match *event {
+        Event::KeyDown { scancode, .. } | Event::KeyUp { scancode, .. } => scancode == Some(key),
+        _ => false,
+    }
+}
+
+/// Returns whether an event reports a mouse button being pressed or released.
+pub(crate) fn reports_mouse_button(event: &Event, button: MouseButton) -> bool {
+    match *event {
+        Event::MouseButtonDown { mouse_btn, .. } | Event::MouseButtonUp { mouse_btn, .. } => {
+            mouse_btn == button
+        }
+        _ => false,
+    }
+}
+
+/// Generates the events the keyboard and mouse would have sent for changes to the input state.
+pub(crate) struct Synthetic<'a> {
+    input: &'a InputState,
+    timestamp: u32,
+    window_id: u32,
+    events: Vec<Event>,
+}
+
+impl<'a> Synthetic<'a> {
+    /// Start generating events for the current input state, in the window with ID `window_id`.
+    pub fn new(input: &'a InputState, window_id: u32) -> Self {
+        Self {
+            input,
+            // Safety: This only reads SDL's timer, and can be called at any time.
+            timestamp: unsafe { sys::SDL_GetTicks() },
+            window_id,
+            events: Vec::new(),
+        }
+    }
+
+    /// Add key events for the keys pressed or released this frame that `filter` returns true
+    /// for.
+    pub fn keys(&mut self, filter: impl Fn(Scancode) -> bool) {
+        let keyboard = &self.input.keyboard;
+        let keymod = MODIFIERS
+            .iter()
+            .filter(|&&(key, _)| keyboard[key].held)
+            .fold(Mod::NOMOD, |keymod, &(_, flag)| keymod | flag);
+        let (timestamp, window_id) = (self.timestamp, self.window_id);
+
+        let mut released: Vec<Scancode> = keyboard
+            .released()
+            .copied()
+            .filter(|&k| filter(k))
+            .collect();
+        released.sort_by_key(|&k| k as i32);
+        self.events
+            .extend(released.into_iter().map(|key| Event::KeyUp {
+                timestamp,
+                window_id,
+                keycode: Keycode::from_scancode(key),
+                scancode: Some(key),
+                keymod,
+                repeat: false,
+            }));
+
+        let mut pressed: Vec<Scancode> =
+            keyboard.pressed().copied().filter(|&k| filter(k)).collect();
+        pressed.sort_by_key(|&k| k as i32);
+        self.events
+            .extend(pressed.into_iter().map(|key| Event::KeyDown {
+                timestamp,
+                window_id,
+                keycode: Keycode::from_scancode(key),
+                scancode: Some(key),
+                keymod,
+                repeat: false,
+            }));
+    }
+
+    /// Add mouse button events for the buttons pressed or released this frame that `filter`
+    /// returns true for.
+    pub fn mouse_buttons(&mut self, filter: impl Fn(MouseButton) -> bool) {
+        let mouse = &self.input.mouse;
+        let (timestamp, window_id) = (self.timestamp, self.window_id);
+        let (x, y) = (mouse.x, mouse.y);
+
+        let mut released: Vec<MouseButton> = mouse
+            .buttons
+            .released()
+            .copied()
+            .filter(|&b| filter(b))
+            .collect();
+        released.sort_by_key(|&b| b as u8);
+        self.events
+            .extend(released.into_iter().map(|mouse_btn| Event::MouseButtonUp {
+                timestamp,
+                window_id,
+                which: 0,
+                mouse_btn,
+                clicks: 1,
+                x,
+                y,
+            }));
+
+        let mut pressed: Vec<MouseButton> = mouse
+            .buttons
+            .pressed()
+            .copied()
+            .filter(|&b| filter(b))
+            .collect();
+        pressed.sort_by_key(|&b| b as u8);
+        self.events
+            .extend(pressed.into_iter().map(|mouse_btn| Event::MouseButtonDown {
+                timestamp,
+                window_id,
+                which: 0,
+                mouse_btn,
+                clicks: 1,
+                x,
+                y,
+            }));
+    }
+
+    /// Add a motion event if the mouse moved this frame.
+    pub fn mouse_motion(&mut self) {
+        let mouse = &self.input.mouse;
+        if (mouse.dx, mouse.dy) == (0, 0) {
+            return;
+        }
+        let state = mouse
+            .buttons
+            .held()
+            .fold(0, |state, &b| state | 1 << (b as u8 - 1));
+        self.events.push(Event::MouseMotion {
+            timestamp: self.timestamp,
+            window_id: self.window_id,
+            which: 0,
+            mousestate: SdlMouseState::from_sdl_state(state),
+            x: mouse.x,
+            y: mouse.y,
+            xrel: mouse.dx,
+            yrel: mouse.dy,
+        });
+    }
+
+    /// Add a wheel event if the mouse wheel was scrolled this frame.
+    pub fn mouse_wheel(&mut self) {
+        let mouse = &self.input.mouse;
+        let (x, y) = (mouse.wheel_x.round() as i32, mouse.wheel_y.round() as i32);
+        if (x, y) == (0, 0) {
+            return;
+        }
+        self.events.push(Event::MouseWheel {
+            timestamp: self.timestamp,
+            window_id: self.window_id,
+            which: 0,
+            x,
+            y,
+            direction: MouseWheelDirection::Normal,
+        });
+    }
+
+    /// Add a text input event, if `text` isn't empty.
+    pub fn text(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        self.events.push(Event::TextInput {
+            timestamp: self.timestamp,
+            window_id: self.window_id,
+            text: text.to_string(),
+        });
+    }
+
+    /// Get the generated events, in the order they were added.
+    pub fn finish(self) -> Vec<Event> {
+        self.events
+    }
+}
@@ -0,0 +1,89 @@
+//! Types related to text input.
+
+use crate::Event;
+
+/// Text which is being composed with an input method, but hasn't been entered yet. This is used
+/// to type languages such as Chinese and Japanese, and should be drawn where the text is being
+/// entered, with the cursor or selection shown.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Composition {
+    /// The text being composed.
+    pub text: String,
+    /// Where the cursor, or the start of the selection, is in the text, in characters.
+    pub start: usize,
+    /// The length of the selection, in characters.
+    pub length: usize,
+}
+
+/// The text entered this frame, and any text being composed.
+#[derive(Default)]
+pub(crate) struct TextState {
+    /// Text entered this frame.
+    pub input: String,
+    /// Text being composed, which persists between frames until it is entered or cancelled.
+    pub composition: Composition,
+}
+
+impl TextState {
+    /// Collect the text entered this frame, replacing the text from the previous frame. This is
+    /// called internally by the engine every frame.
+    pub fn update(&mut self, events: &[Event]) {
+        self.input.clear();
+        for event in events {
+            match event {
+                Event::TextInput { text, .. } => {
+                    self.input.push_str(text);
+                    self.composition = Composition::default();
+                }
+                Event::TextEditing {
+                    text,
+                    start,
+                    length,
+                    ..
+                } => {
+                    self.composition = Composition {
+                        text: text.clone(),
+                        start: (*start).max(0) as usize,
+                        length: (*length).max(0) as usize,
+                    };
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Entered text is collected per frame, and ends any composition.
+    #[test]
+    fn test_update() {
+        let input = |text: &str| Event::TextInput {
+            timestamp: 0,
+            window_id: 0,
+            text: text.to_string(),
+        };
+        let editing = Event::TextEditing {
+            timestamp: 0,
+            window_id: 0,
+            text: "にほ".to_string(),
+            start: 2,
+            length: 0,
+        };
+
+        let mut state = TextState::default();
+        state.update(&[input("a"), input("b"), editing]);
+        assert_eq!(state.input, "ab");
+        assert_eq!(state.composition.text, "にほ");
+        assert_eq!(state.composition.start, 2);
+
+        state.update(&[input("日本")]);
+        assert_eq!(state.input, "日本");
+        assert_eq!(state.composition, Composition::default());
+
+        state.update(&[]);
+        assert_eq!(state.input, "");
+    }
+}
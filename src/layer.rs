@@ -0,0 +1,399 @@
+use std::collections::BTreeMap;
+
+use sdl2::{
+    pixels::PixelFormatEnum,
+    render::{BlendMode, WindowCanvas},
+};
+
+use crate::{engine::Engine, with_engine, with_engine_mut, with_target, Color, Rect, Texture};
+
+/// How a layer is composited onto the canvas.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Layer {
+    /// Layers are drawn in increasing order, so layers with a higher order are drawn on top.
+    /// Layers with the same order are drawn in order of their names.
+    pub order: i32,
+    /// Whether the layer is drawn.
+    pub visible: bool,
+    /// The opacity of the whole layer, from 0 (invisible) to 255 (opaque).
+    pub opacity: u8,
+    /// The color the layer is multiplied by. White leaves it unchanged.
+    pub tint: Color,
+    /// How the layer is blended with the layers below it.
+    pub blend_mode: BlendMode,
+}
+
+impl Layer {
+    /// Create the settings for a visible, opaque, untinted layer with the given order.
+    pub fn new(order: i32) -> Self {
+        Self {
+            order,
+            visible: true,
+            opacity: 255,
+            tint: Color::WHITE,
+            blend_mode: BlendMode::Blend,
+        }
+    }
+}
+
+impl Default for Layer {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+/// A layer and the texture it is drawn to.
+pub(crate) struct LayerState {
+    /// This is `None` while the layer is being drawn to.
+    texture: Option<Texture>,
+    settings: Layer,
+}
+
+/// Create a new, transparent layer with the given name and settings, replacing any existing layer
+/// with the same name.
+///
+/// Drawing can be directed to the layer with [`with_layer`]. Layers keep their contents between
+/// frames, and are drawn over the canvas when each frame is presented, after everything else, so
+/// anything that should appear below other drawing must be drawn to a layer with a lower order.
+/// [`screenshot`][crate::screenshot] includes the layers, as they would be presented.
+///
+/// Layers are the size of the drawing area, and are stretched over the canvas' whole drawing
+/// area. If there is no logical size, they are resized along with the window, keeping their
+/// contents in the top left corner.
+pub fn create_layer(name: &str, settings: Layer) -> Result<(), String> {
+    let (width, height) = with_engine(|engine| engine.drawing_size())?;
+    let texture = Texture::new_target(width, height)?;
+    let old = with_engine_mut(|engine| {
+        let layer = LayerState {
+            texture: Some(texture),
+            settings,
+        };
+        engine.layers.insert(name.to_string(), layer)
+    });
+    // Dropped while the engine isn't borrowed, so the texture can be destroyed
+    drop(old);
+    Ok(())
+}
+
+/// Remove a layer, returning whether it existed.
+pub fn remove_layer(name: &str) -> bool {
+    let old = with_engine_mut(|engine| engine.layers.remove(name));
+    old.is_some()
+}
+
+/// Get the settings of a layer, or `None` if it doesn't exist.
+pub fn layer(name: &str) -> Option<Layer> {
+    with_engine(|engine| engine.layers.get(name).map(|l| l.settings))
+}
+
+/// Change the settings of an existing layer.
+pub fn set_layer(name: &str, settings: Layer) -> Result<(), String> {
+    with_engine_mut(|engine| {
+        let layer = engine.layers.get_mut(name).ok_or_else(|| no_layer(name))?;
+        layer.settings = settings;
+        Ok(())
+    })
+}
+
+/// Run a function with all drawing directed to a layer, as with
+/// [`with_target`][crate::with_target]. Use [`clear`][crate::clear] with a transparent color to
+/// clear the layer.
+pub fn with_layer<F, R>(name: &str, f: F) -> Result<R, String>
+where
+    F: FnOnce() -> R,
+{
+    let mut texture = with_engine_mut(|engine| {
+        let layer = engine.layers.get_mut(name).ok_or_else(|| no_layer(name))?;
+        layer
+            .texture
+            .take()
+            .ok_or_else(|| format!("Layer \"{}\" is already being drawn to", name))
+    })?;
+
+    let res = with_target(&mut texture, f);
+
+    let unused = with_engine_mut(|engine| match engine.layers.get_mut(name) {
+        Some(layer) if layer.texture.is_none() => {
+            layer.texture = Some(texture);
+            None
+        }
+        // The layer was removed or replaced while it was being drawn to
+        _ => Some(texture),
+    });
+    drop(unused);
+    res
+}
+
+fn no_layer(name: &str) -> String {
+    format!("No layer is named \"{}\"", name)
+}
+
+/// Returns whether any layer would be drawn.
+fn any_visible(engine: &Engine) -> bool {
+    engine
+        .layers
+        .values()
+        .any(|l| l.settings.visible && l.texture.is_some())
+}
+
+/// Draw every visible layer over the canvas. Called internally by the engine every frame, before
+/// the frame is presented.
+pub(crate) fn composite(engine: &mut Engine) -> Result<(), String> {
+    let area = engine.logical.as_ref().map(|l| l.fit.output);
+    draw_layers(&mut engine.canvas, &mut engine.layers, engine.id, area)
+}
+
+/// Draw every visible layer over the current render target, stretched over `area` in output
+/// pixels, or the whole target if it is `None`.
+fn draw_layers(
+    canvas: &mut WindowCanvas,
+    layers: &mut BTreeMap<String, LayerState>,
+    engine_id: u64,
+    area: Option<Rect>,
+) -> Result<(), String> {
+    let mut layers: Vec<&mut LayerState> = layers
+        .values_mut()
+        .filter(|l| l.settings.visible && l.texture.is_some())
+        .collect();
+    if layers.is_empty() {
+        return Ok(());
+    }
+    // Sorting is stable, so layers with the same order stay sorted by name
+    layers.sort_by_key(|l| l.settings.order);
+
+    let scale = canvas.scale();
+    let viewport = canvas.viewport();
+    let clip = canvas.clip_rect();
+    canvas.set_scale(1.0, 1.0)?;
+    canvas.set_viewport(None);
+    canvas.set_clip_rect(None);
+
+    let mut res = Ok(());
+    for layer in layers {
+        let settings = layer.settings;
+        let texture = layer.texture.as_mut().unwrap().get_mut(engine_id)?;
+        let (r, g, b) = settings.tint.rgb();
+        texture.set_color_mod(r, g, b);
+        texture.set_alpha_mod(settings.opacity);
+        texture.set_blend_mode(settings.blend_mode);
+        res = res.and(canvas.copy(texture, None, area));
+    }
+
+    canvas.set_scale(scale.0, scale.1)?;
+    canvas.set_viewport(viewport);
+    canvas.set_clip_rect(clip);
+    res
+}
+
+/// Draw the visible layers over pixels read from the canvas, as [`composite`] will when the frame
+/// is presented, without changing the canvas. Called internally when capturing the canvas.
+pub(crate) fn flatten(
+    engine: &mut Engine,
+    (width, height): (u32, u32),
+    format: PixelFormatEnum,
+    pixels: Vec<u8>,
+) -> Result<Vec<u8>, String> {
+    if !any_visible(engine) {
+        return Ok(pixels);
+    }
+
+    let creator = &engine.texture_creator;
+    let mut canvas_copy = creator
+        .create_texture_static(format, width, height)
+        .map_err(|e| e.to_string())?;
+    let mut flattened = creator
+        .create_texture_target(PixelFormatEnum::ARGB8888, width, height)
+        .map_err(|e| e.to_string())?;
+
+    let area = engine.logical.as_ref().map(|l| l.fit.output);
+    let (layers, id) = (&mut engine.layers, engine.id);
+    let mut res = Err(String::new());
+    let pitch = width as usize * format.byte_size_per_pixel();
+    let updated = canvas_copy
+        .update(None, &pixels, pitch)
+        .map_err(|e| e.to_string());
+    canvas_copy.set_blend_mode(BlendMode::None);
+    if updated.is_ok() {
+        let drawn = engine.canvas.with_texture_canvas(&mut flattened, |canvas| {
+            res = canvas
+                .copy(&canvas_copy, None, None)
+                .and_then(|()| draw_layers(canvas, layers, id, area))
+                .and_then(|()| canvas.read_pixels(None, format));
+        });
+        res = drawn.map_err(|e| e.to_string()).and(res);
+    }
+
+    // Safety: Both textures were created by the engine's texture creator, which still exists.
+    unsafe {
+        canvas_copy.destroy();
+        flattened.destroy();
+    }
+    updated.and(res)
+}
+
+/// Resize every layer to the drawing size, keeping their contents in the top left corner. Called
+/// internally by the engine when the window is resized and there is no logical size.
+pub(crate) fn resize(engine: &mut Engine) -> Result<(), String> {
+    let (width, height) = engine.drawing_size()?;
+    let Engine {
+        id,
+        ref mut canvas,
+        ref texture_creator,
+        ref mut layers,
+        ..
+    } = *engine;
+
+    for layer in layers.values_mut() {
+        let old = match layer.texture {
+            Some(ref mut texture) => texture.get_mut(id)?,
+            None => continue,
+        };
+        let query = old.query();
+        if (query.width, query.height) == (width, height) {
+            continue;
+        }
+        let mut new = texture_creator
+            .create_texture_target(PixelFormatEnum::ARGB8888, width, height)
+            .map_err(|e| e.to_string())?;
+        new.set_blend_mode(BlendMode::Blend);
+        old.set_blend_mode(BlendMode::None);
+
+        let mut res = Ok(());
+        let drawn = canvas.with_texture_canvas(&mut new, |canvas| {
+            canvas.set_draw_color(Color::RGBA(0, 0, 0, 0));
+            canvas.clear();
+            let kept = Rect::new(0, 0, query.width, query.height);
+            res = canvas.copy(old, None, kept);
+        });
+        // The old texture is destroyed once the engine is no longer borrowed
+        layer.texture = Some(Texture::from_sdl(new, id));
+        drawn.map_err(|e| e.to_string()).and(res)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{screenshot, test_util, Rect};
+
+    /// Run an application headless in a 4x4 window for `frames` frames.
+    fn run<A: crate::Application>(frames: u64, app: &mut A) {
+        test_util::run(|| {
+            crate::Builder::new("Test", 4, 4)
+                .headless(true)
+                .resizable(true)
+                .max_frames(frames)
+                .start(app)
+                .unwrap();
+        });
+    }
+
+    /// Layers are composited in order, with their settings applied, and included in screenshots.
+    #[test]
+    fn test_composite() {
+        let mut app = |_| {
+            crate::clear(Color::BLACK);
+            create_layer("top", Layer::new(1))?;
+            create_layer("bottom", Layer::new(0))?;
+            create_layer("hidden", Layer::new(2))?;
+            with_layer("top", || {
+                crate::fill_rect(Rect::new(0, 0, 2, 4), Color::RED)
+            })??;
+            with_layer("bottom", || crate::clear(Color::GREEN))?;
+            with_layer("hidden", || crate::clear(Color::WHITE))?;
+            set_layer(
+                "hidden",
+                Layer {
+                    visible: false,
+                    ..Layer::new(2)
+                },
+            )?;
+            set_layer(
+                "bottom",
+                Layer {
+                    tint: Color::RGB(255, 0, 255),
+                    ..Layer::new(0)
+                },
+            )?;
+
+            let shot = screenshot()?;
+            assert_eq!(shot.pixel(0, 0), Some(Color::RED));
+            // Tinting green with magenta leaves black
+            assert_eq!(shot.pixel(3, 0), Some(Color::BLACK));
+            Ok(true)
+        };
+        run(1, &mut app);
+    }
+
+    /// Opacity and blend modes apply to the whole layer, and layers with the same order are drawn
+    /// in order of their names.
+    #[test]
+    fn test_settings() {
+        let mut app = |_| {
+            crate::clear(Color::GREEN);
+            create_layer("faded", Layer::new(0))?;
+            create_layer("added", Layer::new(1))?;
+            create_layer("tie_a", Layer::new(2))?;
+            create_layer("tie_b", Layer::new(2))?;
+            with_layer("faded", || {
+                crate::fill_rect(Rect::new(0, 0, 4, 1), Color::WHITE)
+            })??;
+            with_layer("added", || {
+                crate::fill_rect(Rect::new(0, 1, 4, 1), Color::RED)
+            })??;
+            with_layer("tie_a", || {
+                crate::fill_rect(Rect::new(0, 2, 4, 1), Color::RED)
+            })??;
+            with_layer("tie_b", || {
+                crate::fill_rect(Rect::new(0, 2, 4, 1), Color::BLUE)
+            })??;
+            set_layer(
+                "faded",
+                Layer {
+                    opacity: 0,
+                    ..Layer::new(0)
+                },
+            )?;
+            set_layer(
+                "added",
+                Layer {
+                    blend_mode: BlendMode::Add,
+                    ..Layer::new(1)
+                },
+            )?;
+
+            let shot = screenshot()?;
+            assert_eq!(shot.pixel(0, 0), Some(Color::GREEN));
+            assert_eq!(shot.pixel(0, 1), Some(Color::YELLOW));
+            assert_eq!(shot.pixel(0, 2), Some(Color::BLUE));
+            Ok(true)
+        };
+        run(1, &mut app);
+    }
+
+    /// Without a logical size, layers are resized with the window and keep their contents.
+    #[test]
+    fn test_resize() {
+        let mut frame = 0;
+        let mut app = |_| {
+            crate::clear(Color::BLACK);
+            if frame == 0 {
+                create_layer("layer", Layer::new(0))?;
+                with_layer("layer", || {
+                    crate::fill_rect(Rect::new(0, 0, 2, 2), Color::RED)
+                })??;
+                crate::set_window_size(8, 8)?;
+            } else {
+                let shot = screenshot()?;
+                assert_eq!(shot.size(), (8, 8));
+                assert_eq!(shot.pixel(1, 1), Some(Color::RED));
+                assert_eq!(shot.pixel(3, 3), Some(Color::BLACK));
+            }
+            frame += 1;
+            Ok(true)
+        };
+        run(2, &mut app);
+        assert_eq!(frame, 2);
+    }
+}
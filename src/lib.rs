@@ -1,10 +1,26 @@
+//! A minimal game engine inspired by the OLC Pixel Game Engine, built on SDL2.
+//!
+//! # Requirements
+//!
+//! SGE needs SDL 2.0.10 or later, since it uses SDL's floating point rendering functions. The
+//! `gfx` and `image` features, which are enabled by default, also need SDL2_gfx and SDL2_image.
+//! The `bundled_sdl` feature builds SDL from source instead of using the system's copy.
+
 mod builder;
 pub use builder::Builder;
 mod draw;
 pub use draw::*;
 mod engine;
 use engine::Engine;
+mod float;
+pub use float::{
+    draw_line_f, draw_lines_f, draw_point_f, draw_points_f, draw_rect_f, draw_rects_f, fill_rect_f,
+    fill_rects_f, FPoint, FRect,
+};
 mod fps;
+pub use fps::{frame_stats, set_target_fps, target_fps, FrameStats};
+mod framebuffer;
+pub use framebuffer::{draw_framebuffer, resize_framebuffer, with_framebuffer, Framebuffer};
 mod fullscreen;
 pub use fullscreen::Fullscreen;
 #[cfg(feature = "gfx")]
@@ -13,11 +29,28 @@ mod gfx;
 pub use gfx::*;
 pub mod input;
 pub use input::functions::*;
+mod layer;
+pub use layer::{create_layer, layer, remove_layer, set_layer, with_layer, Layer};
 pub mod panic;
+mod scaling;
+pub use scaling::{logical_to_window, window_to_logical, ScaleMode};
+mod screenshot;
+pub use screenshot::screenshot;
 mod surface;
 pub use surface::*;
+#[cfg(feature = "testing")]
+pub mod testing;
 mod texture;
 pub use texture::*;
+mod timestep;
+pub use timestep::interpolation_alpha;
+mod transform;
+pub use transform::{
+    camera, pop_transform, push_transform, screen_to_world, set_camera, transform, world_to_screen,
+    Camera, Transform,
+};
+mod window;
+pub use window::*;
 
 use std::cell::RefCell;
 
@@ -29,12 +62,14 @@ pub use sdl2::{
     render::TextureValueError,
 };
 
-use once_cell::unsync::OnceCell;
-
 thread_local! {
-        static ENGINE: OnceCell<RefCell<Engine>> = OnceCell::new();
+        static ENGINE: RefCell<Option<Engine>> = const { RefCell::new(None) };
 }
 
+/// Only one SDL context can exist at a time, so tests which start an engine take this lock.
+#[cfg(any(test, feature = "testing"))]
+static TEST_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
 /// Panic message
 const NOT_INIT: &str = "No engine is initialised in this thread";
 
@@ -44,7 +79,7 @@ const NOT_INIT: &str = "No engine is initialised in this thread";
 ///
 /// -   An engine is not running in the current thread
 /// -   The engine is already borrowed mutably. Internally the engine is stored in a [`RefCell`],
-/// so the borrowing rules are checked at runtime.
+///     so the borrowing rules are checked at runtime.
 ///
 /// Note that almost all sge functions internally call either this or [`with_engine_mut`], and
 /// will thus *also* panic in the above situations.
@@ -53,8 +88,8 @@ where
     F: FnOnce(&Engine) -> R,
 {
     ENGINE.with(|e| {
-        let engine = e.get().expect(NOT_INIT).borrow();
-        f(&*engine)
+        let engine = e.borrow();
+        f(engine.as_ref().expect(NOT_INIT))
     })
 }
 
@@ -64,7 +99,7 @@ where
 ///
 /// -   An engine is not running in the current thread
 /// -   The engine is already borrowed (either mutably or immutably). Internally the engine is
-/// stored in a [`RefCell`], so the borrowing rules are checked at runtime.
+///     stored in a [`RefCell`], so the borrowing rules are checked at runtime.
 ///
 /// Note that almost all sge functions internally call either this or [`with_engine`], and will
 /// thus *also* panic in the above situations.
@@ -73,11 +108,18 @@ where
     F: FnOnce(&mut Engine) -> R,
 {
     ENGINE.with(|e| {
-        let mut engine = e.get().expect(NOT_INIT).borrow_mut();
-        f(&mut *engine)
+        let mut engine = e.borrow_mut();
+        f(engine.as_mut().expect(NOT_INIT))
     })
 }
 
+/// Ask the application to quit, as if the user had closed the window. This can be called from
+/// anywhere, and takes effect at the end of the current frame.
+/// [`Application::on_quit_requested`] is called first, and can cancel the request.
+pub fn request_quit() {
+    with_engine_mut(|e| e.quit_requested = true)
+}
+
 /// The return type of most functions in the [`Application`] trait.
 ///
 /// * Returning `Ok(true` continues the application.
@@ -102,14 +144,59 @@ pub trait Application {
         Ok(true)
     }
 
-    /// Called for each [`Event`] every frame.
+    /// Called zero or more times per frame, before [`on_update`](Self::on_update), if a fixed
+    /// timestep is set with [`Builder::fixed_timestep`][crate::Builder::fixed_timestep].
+    ///
+    /// `step` is the fixed duration (in seconds) of each update. Unlike `on_update`, this is
+    /// deterministic, making it suitable for physics and networked game logic.
+    fn on_fixed_update(&mut self, _step: f64) -> ApplicationResult {
+        Ok(true)
+    }
+
+    /// Called when the user asks to quit, such as by closing the window, or when [`request_quit`]
+    /// is called. This isn't called if [`on_event`](Self::on_event) handles the [`Event::Quit`]
+    /// event itself.
+    ///
+    /// Returning `Ok(false)` (the default) lets the application quit, while returning `Ok(true)`
+    /// cancels the request and continues the application. This can be used to ask the user to
+    /// save their work, for example.
+    fn on_quit_requested(&mut self) -> ApplicationResult {
+        Ok(false)
+    }
+
+    /// Called once when the application quits, before the engine is destroyed. The engine is still
+    /// running, so you can use SGE functions here, such as to save state or free textures.
     ///
-    /// You shouldn't call any other SGE functions from this handler. Doing so will panic because
-    /// the engine is already borrowed. This can be used to process any type of SDL event, such as
-    /// text input.
+    /// This is called however the application quits, including if an error was returned from
+    /// another handler.
+    fn on_destroy(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    /// Called when the window is resized, with its new width and height. This is called before
+    /// [`on_event`](Self::on_event) receives the corresponding [`Event`].
+    fn on_resize(&mut self, _width: u32, _height: u32) -> ApplicationResult {
+        Ok(true)
+    }
+
+    /// Called for each [`Event`] every frame, before [`on_update`](Self::on_update).
+    ///
+    /// This can be used to process any type of SDL event, such as text input. Events are
+    /// collected before being dispatched, so any other SGE function can be used from this
+    /// handler, and the input state (such as [`key`]) is already up to date.
+    ///
+    /// Returning `Ok(true)` indicates that the event was handled, while returning `Ok(false)`
+    /// lets the engine handle it instead.
     fn on_event(&mut self, _event: &Event) -> ApplicationResult {
         Ok(false)
     }
+
+    /// Called when a screenshot is taken with the
+    /// [screenshot key][crate::Builder::screenshot_key], with the path it was saved to, or why it
+    /// couldn't be saved. This is called before [`on_event`](Self::on_event).
+    fn on_screenshot(&mut self, _result: Result<String, String>) -> ApplicationResult {
+        Ok(true)
+    }
 }
 
 impl<F> Application for F
@@ -121,10 +208,44 @@ where
     }
 }
 
+/// Helpers for tests which need to start an engine.
+#[cfg(test)]
+pub(crate) mod test_util {
+    use crate::{screenshot, Builder, Surface};
+
+    /// Run `f`, making sure no other test is running an engine at the same time.
+    pub fn run<F: FnOnce()>(f: F) {
+        let _guard = super::TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        f()
+    }
+
+    /// Draw a single headless frame with `draw`, then pass a screenshot of it to `check`.
+    pub fn check_frame<D, C>(width: u32, height: u32, draw: D, check: C)
+    where
+        D: FnOnce() -> Result<(), String>,
+        C: FnOnce(&Surface),
+    {
+        run(|| {
+            let mut draw = Some(draw);
+            let mut check = Some(check);
+            let mut app = |_| {
+                draw.take().unwrap()()?;
+                check.take().unwrap()(&screenshot()?);
+                Ok(true)
+            };
+            Builder::new("Test", width, height)
+                .headless(true)
+                .max_frames(1)
+                .start(&mut app)
+                .unwrap();
+        });
+    }
+}
+
 /// Commonly used types.
 pub mod prelude {
     pub use crate::{
         input::{MouseButton, Scancode},
-        Color, Point, Rect, Texture,
+        Color, FPoint, FRect, Point, Rect, Texture,
     };
 }
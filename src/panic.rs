@@ -11,8 +11,8 @@ pub fn install_hook() {
         let msg = format!("Thread '{}' {}", thread_name, info);
         ENGINE
             .try_with(|e| {
-                let engine = e.get().and_then(|e| e.try_borrow().ok());
-                if let Some(e) = engine {
+                let engine = e.try_borrow().ok();
+                if let Some(e) = engine.as_ref().and_then(|e| e.as_ref()) {
                     // Use the parent window
                     show_simple_message_box(
                         MessageBoxFlag::ERROR,
@@ -0,0 +1,281 @@
+use sdl2::render::WindowCanvas;
+
+use crate::{with_engine, Color, Point, Rect};
+
+/// Convert a point in window coordinates, such as a mouse position reported by SDL, to the
+/// coordinates used for drawing.
+///
+/// This accounts for the [scale][crate::Builder::scale], the current
+/// [viewport][crate::set_viewport], and any [logical size][crate::Builder::logical_size] and its
+/// bars.
+pub fn window_to_logical(p: Point) -> Point {
+    with_engine(|e| Mapping::new(&e.canvas).to_logical(p))
+}
+
+/// Convert a point in the coordinates used for drawing to window coordinates. This is the inverse
+/// of [`window_to_logical`].
+pub fn logical_to_window(p: Point) -> Point {
+    with_engine(|e| Mapping::new(&e.canvas).to_window(p))
+}
+
+/// How a logical resolution set with [`Builder::logical_size`][crate::Builder::logical_size] is
+/// scaled to fit the window.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ScaleMode {
+    /// Scale by the largest whole number that fits in the window, filling the rest of the window
+    /// with bars. This keeps pixel art crisp. If the window is smaller than the logical size, it
+    /// is scaled down to fit as with [`Letterbox`](Self::Letterbox).
+    Integer,
+    /// Scale as large as fits in the window while keeping the aspect ratio, filling the rest of
+    /// the window with bars.
+    Letterbox,
+    /// Stretch to fill the entire window, ignoring the aspect ratio.
+    Stretch,
+}
+
+/// A fixed logical resolution, and how it currently fits in the window.
+pub(crate) struct Logical {
+    pub size: (u32, u32),
+    pub mode: ScaleMode,
+    pub bar_color: Color,
+    pub fit: Fit,
+    /// The viewport set with [`set_viewport`][crate::set_viewport], relative to the logical area.
+    pub viewport: Option<Rect>,
+}
+
+impl Logical {
+    pub fn new(size: (u32, u32), mode: ScaleMode, bar_color: Color) -> Self {
+        Self {
+            size,
+            mode,
+            bar_color,
+            fit: fit((size.0, size.1), size, mode),
+            viewport: None,
+        }
+    }
+
+    /// Fit the logical resolution to the canvas' current size, and set the canvas' scale and
+    /// viewport accordingly. Any viewport set with [`set_viewport`][crate::set_viewport] is kept,
+    /// relative to the logical area.
+    pub fn apply(&mut self, canvas: &mut WindowCanvas) -> Result<(), String> {
+        let output = canvas.output_size()?;
+        self.fit = fit(output, self.size, self.mode);
+        canvas.set_scale(self.fit.scale.0, self.fit.scale.1)?;
+        canvas.set_viewport(self.viewport_rect(self.viewport));
+        Ok(())
+    }
+
+    /// Get the canvas viewport for a viewport relative to the logical area, or the whole logical
+    /// area if it is `None`.
+    pub fn viewport_rect(&self, viewport: Option<Rect>) -> Rect {
+        let area = self.fit.viewport;
+        viewport.map_or(area, |r| {
+            Rect::new(r.x() + area.x(), r.y() + area.y(), r.width(), r.height())
+        })
+    }
+
+    /// Fill the parts of the canvas outside the logical area with the bar color.
+    pub fn draw_bars(&self, canvas: &mut WindowCanvas) -> Result<(), String> {
+        let (width, height) = canvas.output_size()?;
+        let (width, height) = (width as i32, height as i32);
+        let out = self.fit.output;
+        let bars = [
+            (0, 0, width, out.top()),
+            (0, out.bottom(), width, height - out.bottom()),
+            (0, out.top(), out.left(), out.height() as i32),
+            (
+                out.right(),
+                out.top(),
+                width - out.right(),
+                out.height() as i32,
+            ),
+        ];
+        // Zero-sized rects can't be represented, so skip empty bars
+        let bars: Vec<Rect> = bars
+            .iter()
+            .filter(|&&(_, _, w, h)| w > 0 && h > 0)
+            .map(|&(x, y, w, h)| Rect::new(x, y, w as u32, h as u32))
+            .collect();
+        if bars.is_empty() {
+            return Ok(());
+        }
+
+        let scale = canvas.scale();
+        let viewport = canvas.viewport();
+        let clip = canvas.clip_rect();
+        canvas.set_scale(1.0, 1.0)?;
+        canvas.set_viewport(None);
+        canvas.set_clip_rect(None);
+
+        canvas.set_draw_color(self.bar_color);
+        let res = canvas.fill_rects(&bars);
+
+        canvas.set_scale(scale.0, scale.1)?;
+        canvas.set_viewport(viewport);
+        canvas.set_clip_rect(clip);
+        res
+    }
+}
+
+/// How window coordinates map to the coordinates used for drawing on a canvas.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Mapping {
+    /// The ratio of the canvas' output size in pixels to the window's size in screen coordinates.
+    /// This isn't 1.0 on high DPI displays.
+    pub pixel_ratio: (f32, f32),
+    /// The canvas' scale.
+    pub scale: (f32, f32),
+    /// The origin of the canvas' viewport, in scaled coordinates.
+    pub origin: Point,
+}
+
+impl Mapping {
+    /// Get the current mapping of a canvas.
+    pub fn new(canvas: &WindowCanvas) -> Self {
+        let window = canvas.window().size();
+        let output = canvas.output_size().unwrap_or(window);
+        let viewport = canvas.viewport();
+        Self {
+            pixel_ratio: (
+                output.0 as f32 / window.0.max(1) as f32,
+                output.1 as f32 / window.1.max(1) as f32,
+            ),
+            scale: canvas.scale(),
+            origin: Point::new(viewport.x(), viewport.y()),
+        }
+    }
+
+    /// Convert a point in window coordinates to drawing coordinates.
+    pub fn to_logical(self, p: Point) -> Point {
+        let x = p.x() as f32 * self.pixel_ratio.0 / self.scale.0;
+        let y = p.y() as f32 * self.pixel_ratio.1 / self.scale.1;
+        Point::new(
+            x.floor() as i32 - self.origin.x(),
+            y.floor() as i32 - self.origin.y(),
+        )
+    }
+
+    /// Convert a point in drawing coordinates to window coordinates.
+    pub fn to_window(self, p: Point) -> Point {
+        let x = (p.x() + self.origin.x()) as f32 * self.scale.0 / self.pixel_ratio.0;
+        let y = (p.y() + self.origin.y()) as f32 * self.scale.1 / self.pixel_ratio.1;
+        Point::new(x.floor() as i32, y.floor() as i32)
+    }
+}
+
+/// Where a logical area is drawn on the canvas.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Fit {
+    /// The area covered, in output pixels.
+    pub output: Rect,
+    /// The scale from logical coordinates to output pixels.
+    pub scale: (f32, f32),
+    /// The viewport to set on the canvas, in logical coordinates.
+    pub viewport: Rect,
+}
+
+/// Calculate how a logical area of size `logical` fits on a canvas of size `output`.
+pub(crate) fn fit(output: (u32, u32), logical: (u32, u32), mode: ScaleMode) -> Fit {
+    let (out_w, out_h) = (output.0 as f32, output.1 as f32);
+    let (log_w, log_h) = (logical.0.max(1) as f32, logical.1.max(1) as f32);
+
+    let scale = match mode {
+        ScaleMode::Integer => {
+            let fits = (out_w / log_w).min(out_h / log_h);
+            // Scale down to fit if the window is too small for any whole scale
+            let scale = if fits >= 1.0 { fits.floor() } else { fits };
+            (scale, scale)
+        }
+        ScaleMode::Letterbox => {
+            let scale = (out_w / log_w).min(out_h / log_h);
+            (scale, scale)
+        }
+        ScaleMode::Stretch => (out_w / log_w, out_h / log_h),
+    };
+
+    // The viewport is positioned in logical coordinates, so the offset has to be rounded to a
+    // whole number of them
+    let x = ((out_w - log_w * scale.0) / 2.0 / scale.0).floor() as i32;
+    let y = ((out_h - log_h * scale.1) / 2.0 / scale.1).floor() as i32;
+    Fit {
+        output: Rect::new(
+            (x as f32 * scale.0).round() as i32,
+            (y as f32 * scale.1).round() as i32,
+            (log_w * scale.0).round() as u32,
+            (log_h * scale.1).round() as u32,
+        ),
+        scale,
+        viewport: Rect::new(x, y, logical.0, logical.1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Integer scaling uses the largest whole scale that fits, centered.
+    #[test]
+    fn test_fit_integer() {
+        let fit = fit((700, 500), (160, 120), ScaleMode::Integer);
+        assert_eq!(fit.scale, (4.0, 4.0));
+        assert_eq!(fit.output, Rect::new(28, 8, 640, 480));
+        assert_eq!(fit.viewport, Rect::new(7, 2, 160, 120));
+
+        // Windows smaller than the logical size are scaled down to fit
+        let fit = super::fit((80, 45), (160, 120), ScaleMode::Integer);
+        assert_eq!(fit.scale, (0.375, 0.375));
+        assert_eq!(fit.output, Rect::new(10, 0, 60, 45));
+        assert_eq!(fit.viewport, Rect::new(26, 0, 160, 120));
+    }
+
+    /// Letterboxing keeps the aspect ratio, adding bars on the shorter side.
+    #[test]
+    fn test_fit_letterbox() {
+        let fit = fit((800, 400), (200, 200), ScaleMode::Letterbox);
+        assert_eq!(fit.scale, (2.0, 2.0));
+        assert_eq!(fit.output, Rect::new(200, 0, 400, 400));
+        assert_eq!(fit.viewport, Rect::new(100, 0, 200, 200));
+    }
+
+    /// Stretching fills the whole canvas.
+    #[test]
+    fn test_fit_stretch() {
+        let fit = fit((800, 400), (200, 200), ScaleMode::Stretch);
+        assert_eq!(fit.scale, (4.0, 2.0));
+        assert_eq!(fit.output, Rect::new(0, 0, 800, 400));
+        assert_eq!(fit.viewport, Rect::new(0, 0, 200, 200));
+    }
+
+    /// Window coordinates are mapped into the logical area, and back.
+    #[test]
+    fn test_mapping() {
+        let fit = fit((700, 500), (160, 120), ScaleMode::Integer);
+        let mapping = Mapping {
+            pixel_ratio: (1.0, 1.0),
+            scale: fit.scale,
+            origin: Point::new(fit.viewport.x(), fit.viewport.y()),
+        };
+        assert_eq!(mapping.to_logical(Point::new(28, 8)), Point::new(0, 0));
+        assert_eq!(
+            mapping.to_logical(Point::new(667, 487)),
+            Point::new(159, 119)
+        );
+        assert_eq!(mapping.to_window(Point::new(0, 0)), Point::new(28, 8));
+        assert_eq!(
+            mapping.to_window(Point::new(159, 119)),
+            Point::new(664, 484)
+        );
+    }
+
+    /// The pixel ratio of high DPI displays is accounted for.
+    #[test]
+    fn test_mapping_high_dpi() {
+        let mapping = Mapping {
+            pixel_ratio: (2.0, 2.0),
+            scale: (4.0, 4.0),
+            origin: Point::new(5, 0),
+        };
+        assert_eq!(mapping.to_logical(Point::new(100, 50)), Point::new(45, 25));
+        assert_eq!(mapping.to_window(Point::new(45, 25)), Point::new(100, 50));
+    }
+}
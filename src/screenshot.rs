@@ -0,0 +1,135 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use sdl2::{pixels::PixelFormatEnum, surface::Surface as SdlSurface};
+
+use crate::{engine::Engine, layer, texture, with_engine_mut, Surface};
+
+/// Read back the current contents of the canvas into a new RGBA [`Surface`].
+///
+/// This captures everything drawn so far in the current frame, including any
+/// [layers][crate::create_layer] as they will be presented, so it should usually be called at
+/// the end of [`Application::on_update`][crate::Application::on_update]. Reading back pixels is
+/// slow, and shouldn't be done every frame.
+pub fn screenshot() -> Result<Surface<'static>, String> {
+    with_engine_mut(capture)
+}
+
+/// Read back the current contents of the engine's canvas, including the layers that will be drawn
+/// over it. The viewport is temporarily reset so the entire canvas is captured.
+pub(crate) fn capture(engine: &mut Engine) -> Result<Surface<'static>, String> {
+    const FORMAT: PixelFormatEnum = PixelFormatEnum::RGBA32;
+
+    let viewport = engine.canvas.viewport();
+    engine.canvas.set_viewport(None);
+    let (width, height) = engine.canvas.output_size()?;
+    let pixels = engine.canvas.read_pixels(None, FORMAT);
+    engine.canvas.set_viewport(viewport);
+    let pixels = pixels?;
+    // Layers are only drawn over the window, not over textures drawn to with `with_target`
+    let pixels = if texture::target_set(engine) {
+        pixels
+    } else {
+        layer::flatten(engine, (width, height), FORMAT, pixels)?
+    };
+
+    let mut surf = SdlSurface::new(width, height, FORMAT)?;
+    let pitch = surf.pitch() as usize;
+    let row_len = width as usize * FORMAT.byte_size_per_pixel();
+    surf.with_lock_mut(|dst| {
+        for (dst_row, src_row) in dst.chunks_mut(pitch).zip(pixels.chunks_exact(row_len)) {
+            dst_row[..row_len].copy_from_slice(src_row);
+        }
+    });
+    Ok(Surface(surf))
+}
+
+/// Capture the canvas and save it to a file in the working directory, named after the current
+/// time. Called internally by the engine when the screenshot key is pressed.
+pub(crate) fn save_timestamped(engine: &mut Engine) -> Result<String, String> {
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let name = format!("screenshot-{}-{:03}", time.as_secs(), time.subsec_millis());
+    let surf = capture(engine)?;
+
+    #[cfg(feature = "image")]
+    let path = {
+        let path = format!("{}.png", name);
+        surf.save_png(&path)?;
+        path
+    };
+    #[cfg(not(feature = "image"))]
+    let path = {
+        let path = format!("{}.bmp", name);
+        surf.save_bmp(&path)?;
+        path
+    };
+
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        input::Scancode, test_util, with_engine_mut, Application, ApplicationResult, Color,
+    };
+
+    /// A screenshot contains what was drawn in the frame.
+    #[test]
+    fn test_screenshot() {
+        test_util::check_frame(
+            32,
+            16,
+            || {
+                crate::clear(Color::RED);
+                Ok(())
+            },
+            |shot| {
+                assert_eq!(shot.size(), (32, 16));
+                assert_eq!(shot.pixel(0, 0), Some(Color::RED));
+                assert_eq!(shot.pixel(31, 15), Some(Color::RED));
+                assert_eq!(shot.pixel(32, 0), None);
+            },
+        );
+    }
+
+    /// Pressing the screenshot key saves a screenshot, and tells the application where.
+    #[test]
+    fn test_screenshot_key() {
+        struct App {
+            frame: u32,
+            saved: Option<Result<String, String>>,
+        }
+
+        impl Application for App {
+            fn on_update(&mut self, _elapsed_time: f64) -> ApplicationResult {
+                crate::clear(Color::RED);
+                let held = self.frame == 0;
+                with_engine_mut(|e| e.input.injected.keys.insert(Scancode::F12, held));
+                self.frame += 1;
+                Ok(true)
+            }
+
+            fn on_screenshot(&mut self, result: Result<String, String>) -> ApplicationResult {
+                self.saved = Some(result);
+                Ok(true)
+            }
+        }
+
+        test_util::run(|| {
+            let mut app = App {
+                frame: 0,
+                saved: None,
+            };
+            crate::Builder::new("Test", 8, 8)
+                .headless(true)
+                .screenshot_key(Scancode::F12)
+                .max_frames(3)
+                .start(&mut app)
+                .unwrap();
+            let path = app.saved.expect("No screenshot was taken").unwrap();
+            assert!(std::path::Path::new(&path).is_file());
+            std::fs::remove_file(path).unwrap();
+        });
+    }
+}
@@ -2,17 +2,11 @@ use std::path::Path;
 
 use sdl2::{pixels::PixelFormatEnum, surface::Surface as SdlSurface};
 
-use crate::{Color, Texture, TextureValueError, ENGINE, NOT_INIT};
+use crate::{with_engine, Color, Texture, TextureValueError};
 
 /// A helper to get the window's default pixel format.
 fn default_pixel_format() -> PixelFormatEnum {
-    ENGINE.with(|e| {
-        e.get()
-            .expect(NOT_INIT)
-            .borrow()
-            .canvas
-            .default_pixel_format()
-    })
+    with_engine(|e| e.canvas.default_pixel_format())
 }
 
 /// A 2D array of pixels in a given format.
@@ -69,6 +63,46 @@ impl Surface<'_> {
         self.0.set_alpha_mod(a);
     }
 
+    /// Returns the [`Color`][crate::Color] of the pixel at (`x`, `y`), or `None` if the
+    /// coordinates are outside the `Surface`.
+    pub fn pixel(&self, x: u32, y: u32) -> Option<Color> {
+        let (width, height) = self.size();
+        if x >= width || y >= height {
+            return None;
+        }
+
+        let format = self.0.pixel_format();
+        let bpp = self.0.pixel_format_enum().byte_size_per_pixel();
+        let offset = y as usize * self.0.pitch() as usize + x as usize * bpp;
+        let raw = self.0.with_lock(|pixels| {
+            let b = &pixels[offset..offset + bpp];
+            // Pixels are stored as native endian integers, except 24 bit ones which are stored
+            // as 3 bytes in the order of the target's endianness.
+            match bpp {
+                1 => b[0] as u32,
+                2 => u16::from_ne_bytes([b[0], b[1]]) as u32,
+                3 if cfg!(target_endian = "big") => {
+                    (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32
+                }
+                3 => b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16,
+                _ => u32::from_ne_bytes([b[0], b[1], b[2], b[3]]),
+            }
+        });
+        Some(Color::from_u32(&format, raw))
+    }
+
+    /// Save the `Surface` to a BMP file.
+    pub fn save_bmp<P: AsRef<Path>>(&self, file_path: P) -> Result<(), String> {
+        self.0.save_bmp(file_path)
+    }
+
+    /// Save the `Surface` to a PNG file.
+    #[cfg(feature = "image")]
+    pub fn save_png<P: AsRef<Path>>(&self, file_path: P) -> Result<(), String> {
+        use sdl2::image::SaveSurface;
+        self.0.save(file_path)
+    }
+
     /// Loads this surface as a new [`Texture`][crate::Texture], which may be hardware accelerated
     /// and thus faster to render.
     pub fn as_texture(&self) -> Result<Texture, TextureValueError> {
@@ -0,0 +1,508 @@
+//! Utilities for testing applications without a display.
+//!
+//! The [`Harness`] runs an [`Application`] headless for a number of frames, optionally feeding it
+//! scripted input, and returns the final contents of the canvas. This can then be compared
+//! against a reference image with [`compare_golden`].
+//!
+//! Input can also be injected directly with functions such as [`press`] and [`move_mouse`], which
+//! take effect from the next frame.
+//!
+//! Setting the `SGE_UPDATE_GOLDEN` environment variable causes [`compare_golden`] to overwrite the
+//! reference images with the actual output instead of comparing them.
+
+use std::{
+    env,
+    error::Error,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use sdl2::{image::LoadSurface, pixels::PixelFormatEnum, surface::Surface as SdlSurface};
+
+use crate::{
+    input::{MouseButton, Scancode},
+    screenshot, with_engine_mut, Application, ApplicationResult, Builder, Color, Event, Rect,
+    Surface, TEST_LOCK,
+};
+
+/// Environment variable which, if set, causes reference images to be updated.
+const UPDATE_GOLDEN: &str = "SGE_UPDATE_GOLDEN";
+
+/// A single piece of scripted input.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ScriptEvent {
+    /// Hold a key down until it is released.
+    KeyDown(Scancode),
+    /// Release a key held by [`KeyDown`](Self::KeyDown).
+    KeyUp(Scancode),
+    /// Hold a mouse button down until it is released.
+    MouseDown(MouseButton),
+    /// Release a mouse button held by [`MouseDown`](Self::MouseDown).
+    MouseUp(MouseButton),
+    /// Move the mouse cursor to the given window coordinates.
+    MouseMove(i32, i32),
+    /// Enter text, as if it had been typed.
+    Text(String),
+}
+
+impl ScriptEvent {
+    /// Inject this event into the running engine's input state.
+    fn apply(&self) {
+        match *self {
+            Self::KeyDown(key) => press(key),
+            Self::KeyUp(key) => release(key),
+            Self::MouseDown(button) => press_mouse(button),
+            Self::MouseUp(button) => release_mouse(button),
+            Self::MouseMove(x, y) => move_mouse(x, y),
+            Self::Text(ref text) => type_text(text),
+        }
+    }
+}
+
+/// Hold a key down from the next frame until it is [released](release), regardless of the real
+/// state of the keyboard.
+///
+/// Injected input changes the state seen by functions such as [`sge::key`][crate::key], and
+/// generates the [`Event`]s the device would have sent, such as [`Event::KeyDown`], for any
+/// changes it causes.
+pub fn press(key: Scancode) {
+    with_engine_mut(|e| e.input.injected.keys.insert(key, true));
+}
+
+/// Release a key held with [`press`] from the next frame, returning it to the real state of the
+/// keyboard.
+pub fn release(key: Scancode) {
+    with_engine_mut(|e| e.input.injected.keys.remove(&key));
+}
+
+/// Hold a mouse button down from the next frame until it is [released](release_mouse).
+pub fn press_mouse(button: MouseButton) {
+    with_engine_mut(|e| e.input.injected.mouse_buttons.insert(button, true));
+}
+
+/// Release a mouse button held with [`press_mouse`] from the next frame, returning it to the real
+/// state of the mouse.
+pub fn release_mouse(button: MouseButton) {
+    with_engine_mut(|e| e.input.injected.mouse_buttons.remove(&button));
+}
+
+/// Move the mouse cursor to the given window coordinates from the next frame, regardless of
+/// where the real cursor is. The difference from the previous position is reported by
+/// [`sge::mouse_delta`][crate::mouse_delta].
+pub fn move_mouse(x: i32, y: i32) {
+    with_engine_mut(|e| e.input.injected.mouse_pos = Some((x, y)))
+}
+
+/// Enter text on the next frame, as returned by [`sge::text_input`][crate::text_input].
+pub fn type_text(text: &str) {
+    with_engine_mut(|e| e.input.injected.text.push_str(text))
+}
+
+/// Remove all injected input from the next frame, returning to the real state of the input
+/// devices.
+pub fn reset_input() {
+    with_engine_mut(|e| e.input.injected = Default::default())
+}
+
+/// Runs an [`Application`] headless for a fixed number of frames.
+#[must_use = "Harnesses do nothing unless an Application is run with them"]
+pub struct Harness {
+    width: u32,
+    height: u32,
+    frames: u64,
+    script: Vec<(u64, ScriptEvent)>,
+}
+
+impl Harness {
+    /// Create a new `Harness` with a canvas of the given size. By default, a single frame is run.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            frames: 1,
+            script: Vec::new(),
+        }
+    }
+
+    /// Set the number of frames to run. At least one frame is always run.
+    pub fn frames(mut self, frames: u64) -> Self {
+        self.frames = frames.max(1);
+        self
+    }
+
+    /// Add an event to the input script. The event takes effect from the start of `frame`
+    /// (counting from 0), and is visible to [`sge::key`][crate::key] and friends in that frame.
+    pub fn input(mut self, frame: u64, event: ScriptEvent) -> Self {
+        self.script.push((frame, event));
+        self
+    }
+
+    /// Run the application, returning the contents of the canvas at the end of the last frame. If
+    /// the application quits early, the last frame it drew is returned instead.
+    ///
+    /// Only one engine can run at a time, so concurrent runs (such as from tests running in
+    /// parallel) wait for each other.
+    pub fn run<A: Application>(mut self, app: &mut A) -> Result<Surface<'static>, Box<dyn Error>> {
+        let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+        self.script.sort_by_key(|(frame, _)| *frame);
+        let mut scripted = Scripted {
+            app,
+            frame: 0,
+            last_frame: self.frames.saturating_sub(1),
+            script: &self.script,
+            capture: None,
+        };
+        Builder::new("SGE Test Harness", self.width, self.height)
+            .headless(true)
+            .max_frames(self.frames)
+            .start(&mut scripted)?;
+
+        scripted
+            .capture
+            .ok_or_else(|| "The application quit before drawing a frame".into())
+    }
+}
+
+/// Wraps an [`Application`], feeding it scripted input and capturing the last frame.
+struct Scripted<'a, A> {
+    app: &'a mut A,
+    frame: u64,
+    last_frame: u64,
+    script: &'a [(u64, ScriptEvent)],
+    capture: Option<Surface<'static>>,
+}
+
+impl<A> Scripted<'_, A> {
+    /// Inject the input scripted for the current frame.
+    fn inject(&self) {
+        self.script
+            .iter()
+            .filter(|(frame, _)| *frame == self.frame)
+            .for_each(|(_, event)| event.apply());
+    }
+}
+
+impl<A: Application> Application for Scripted<'_, A> {
+    fn on_create(&mut self) -> ApplicationResult {
+        // Input is read before each frame, so this is picked up by the first one
+        self.inject();
+        self.app.on_create()
+    }
+
+    fn on_update(&mut self, elapsed_time: f64) -> ApplicationResult {
+        let running = self.app.on_update(elapsed_time)?;
+        // Capture the last frame drawn, whether the frame limit was reached or the application quit
+        if !running || self.frame == self.last_frame {
+            self.capture = Some(screenshot()?);
+        }
+        if running {
+            self.frame += 1;
+            self.inject();
+        }
+        Ok(running)
+    }
+
+    fn on_fixed_update(&mut self, step: f64) -> ApplicationResult {
+        self.app.on_fixed_update(step)
+    }
+
+    fn on_quit_requested(&mut self) -> ApplicationResult {
+        let running = self.app.on_quit_requested()?;
+        if !running {
+            self.capture = Some(screenshot()?);
+        }
+        Ok(running)
+    }
+
+    fn on_destroy(&mut self) -> Result<(), Box<dyn Error>> {
+        self.app.on_destroy()
+    }
+
+    fn on_resize(&mut self, width: u32, height: u32) -> ApplicationResult {
+        self.app.on_resize(width, height)
+    }
+
+    fn on_event(&mut self, event: &Event) -> ApplicationResult {
+        self.app.on_event(event)
+    }
+
+    fn on_screenshot(&mut self, result: Result<String, String>) -> ApplicationResult {
+        self.app.on_screenshot(result)
+    }
+}
+
+/// An error from comparing an image against a reference.
+#[derive(Debug)]
+pub enum GoldenError {
+    /// The image and reference have different sizes.
+    Size {
+        expected: (u32, u32),
+        actual: (u32, u32),
+    },
+    /// Some pixels differed by more than the tolerance.
+    Mismatch { pixels: usize, diff: PathBuf },
+    /// The reference image could not be loaded, or an image could not be saved.
+    Sdl(String),
+}
+
+impl From<String> for GoldenError {
+    fn from(e: String) -> Self {
+        Self::Sdl(e)
+    }
+}
+
+impl fmt::Display for GoldenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Size { expected, actual } => write!(
+                f,
+                "Image size {}x{} doesn't match reference size {}x{}",
+                actual.0, actual.1, expected.0, expected.1
+            ),
+            Self::Mismatch { pixels, diff } => write!(
+                f,
+                "{} pixels differ from the reference, see {}",
+                pixels,
+                diff.display()
+            ),
+            Self::Sdl(s) => write!(f, "SDL error: {}", s),
+        }
+    }
+}
+
+impl Error for GoldenError {}
+
+/// Compare `actual` against the reference PNG at `reference`. The images match if no color
+/// channel of any pixel differs by more than `tolerance`.
+///
+/// On a mismatch, the actual image is written next to the reference with the extension
+/// `.actual.png`, along with a diff image with the extension `.diff.png`, in which differing
+/// pixels are red.
+pub fn compare_golden<P: AsRef<Path>>(
+    actual: &Surface,
+    reference: P,
+    tolerance: u8,
+) -> Result<(), GoldenError> {
+    let reference = reference.as_ref();
+    if env::var_os(UPDATE_GOLDEN).is_some() {
+        return actual.save_png(reference).map_err(GoldenError::Sdl);
+    }
+
+    let expected = SdlSurface::from_file(reference)
+        .map_err(|e| format!("{} (set {} to create it)", e, UPDATE_GOLDEN))?;
+    let expected = Surface(expected.convert_format(PixelFormatEnum::RGBA32)?);
+    if expected.size() != actual.size() {
+        return Err(GoldenError::Size {
+            expected: expected.size(),
+            actual: actual.size(),
+        });
+    }
+
+    let (diff, pixels) = compare(actual, &expected, tolerance)?;
+    if pixels == 0 {
+        return Ok(());
+    }
+    actual.save_png(reference.with_extension("actual.png"))?;
+    let diff_path = reference.with_extension("diff.png");
+    diff.save_png(&diff_path)?;
+    Err(GoldenError::Mismatch {
+        pixels,
+        diff: diff_path,
+    })
+}
+
+/// Compare two surfaces of the same size, returning a diff image and the number of pixels which
+/// differ by more than `tolerance` in any channel.
+fn compare(
+    actual: &Surface,
+    expected: &Surface,
+    tolerance: u8,
+) -> Result<(Surface<'static>, usize), String> {
+    let (width, height) = actual.size();
+    let mut diff = SdlSurface::new(width, height, PixelFormatEnum::RGBA32)?;
+    let mut pixels = 0;
+
+    for y in 0..height {
+        for x in 0..width {
+            let a = actual.pixel(x, y).unwrap_or(Color::BLACK);
+            let e = expected.pixel(x, y).unwrap_or(Color::BLACK);
+            let (ar, ag, ab, aa) = a.rgba();
+            let (er, eg, eb, ea) = e.rgba();
+            let differs = [(ar, er), (ag, eg), (ab, eb), (aa, ea)]
+                .iter()
+                .any(|&(a, e)| a.abs_diff(e) > tolerance);
+
+            let color = if differs {
+                pixels += 1;
+                Color::RED
+            } else {
+                // Faded version of the image, to show where the differences are
+                let luma = ((ar as u32 + ag as u32 + ab as u32) / 3) as u8;
+                Color::RGB(luma / 4, luma / 4, luma / 4)
+            };
+            diff.fill_rect(Rect::new(x as i32, y as i32, 1, 1), color)?;
+        }
+    }
+
+    Ok((Surface(diff), pixels))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn surface(color: Color) -> Surface<'static> {
+        let mut surf = SdlSurface::new(4, 4, PixelFormatEnum::RGBA32).unwrap();
+        surf.fill_rect(None, color).unwrap();
+        Surface(surf)
+    }
+
+    /// Pixels within the tolerance match, others don't.
+    #[test]
+    fn test_compare_tolerance() {
+        let a = surface(Color::RGB(100, 100, 100));
+        let b = surface(Color::RGB(102, 100, 99));
+
+        let (_, pixels) = compare(&a, &b, 2).unwrap();
+        assert_eq!(pixels, 0);
+        let (diff, pixels) = compare(&a, &b, 1).unwrap();
+        assert_eq!(pixels, 16);
+        assert_eq!(diff.pixel(0, 0), Some(Color::RED));
+    }
+
+    /// Rendered frames match a reference saved from the same output, and differences are found.
+    #[test]
+    fn test_golden() {
+        let draw = |color: Color| {
+            move |_| {
+                crate::clear(Color::BLACK);
+                crate::fill_rect(Rect::new(2, 2, 4, 4), color)?;
+                Ok(true)
+            }
+        };
+        let reference = env::temp_dir().join("sge-test-golden.png");
+
+        let expected = Harness::new(8, 8).run(&mut draw(Color::RED)).unwrap();
+        expected.save_png(&reference).unwrap();
+        let actual = Harness::new(8, 8)
+            .frames(2)
+            .run(&mut draw(Color::RED))
+            .unwrap();
+        compare_golden(&actual, &reference, 0).unwrap();
+
+        let actual = Harness::new(8, 8).run(&mut draw(Color::BLUE)).unwrap();
+        match compare_golden(&actual, &reference, 0) {
+            Err(GoldenError::Mismatch { pixels: 16, diff }) => assert!(diff.exists()),
+            res => panic!("Expected a mismatch of 16 pixels, got {:?}", res),
+        }
+    }
+
+    /// A frame is run and captured even if no frames are asked for.
+    #[test]
+    fn test_zero_frames() {
+        let mut frames = 0;
+        let mut app = |_| {
+            frames += 1;
+            crate::clear(Color::RED);
+            Ok(true)
+        };
+        let capture = Harness::new(4, 4).frames(0).run(&mut app).unwrap();
+        assert_eq!(frames, 1);
+        assert_eq!(capture.pixel(0, 0), Some(Color::RED));
+    }
+
+    /// The last frame is captured when the application quits early.
+    #[test]
+    fn test_capture_on_quit() {
+        let mut frame = 0;
+        let mut app = |_| {
+            frame += 1;
+            crate::clear(if frame == 2 { Color::BLUE } else { Color::RED });
+            Ok(frame < 2)
+        };
+        let capture = Harness::new(4, 4).frames(5).run(&mut app).unwrap();
+        assert_eq!(capture.pixel(0, 0), Some(Color::BLUE));
+    }
+
+    /// Injected input is seen from the next frame.
+    #[test]
+    fn test_injected_input() {
+        let mut frames = Vec::new();
+        let mut app = |_| {
+            let frame = frames.len();
+            frames.push((crate::key(Scancode::Space), crate::text_input()));
+            if frame == 0 {
+                press(Scancode::Space);
+                type_text("hi");
+            }
+            Ok(true)
+        };
+        Harness::new(16, 16)
+            .frames(4)
+            .input(2, ScriptEvent::KeyUp(Scancode::Space))
+            .run(&mut app)
+            .unwrap();
+
+        let (space, text): (Vec<_>, Vec<_>) = frames.into_iter().unzip();
+        assert!(!space[0].held);
+        assert!(space[1].pressed);
+        assert!(space[2].released);
+        assert_eq!(text, vec!["", "hi", "", ""]);
+    }
+
+    /// Injected input generates events.
+    #[test]
+    fn test_injected_events() {
+        struct Events(Vec<(u64, Event)>, u64);
+
+        impl Application for Events {
+            fn on_update(&mut self, _elapsed_time: f64) -> ApplicationResult {
+                self.1 += 1;
+                Ok(true)
+            }
+
+            fn on_event(&mut self, event: &Event) -> ApplicationResult {
+                match event {
+                    Event::KeyDown { .. }
+                    | Event::KeyUp { .. }
+                    | Event::TextInput { .. }
+                    | Event::MouseMotion { .. } => self.0.push((self.1, event.clone())),
+                    _ => {}
+                }
+                Ok(true)
+            }
+        }
+
+        let mut app = Events(Vec::new(), 0);
+        Harness::new(16, 16)
+            .frames(3)
+            .input(1, ScriptEvent::KeyDown(Scancode::A))
+            .input(1, ScriptEvent::Text("a".to_string()))
+            .input(1, ScriptEvent::MouseMove(3, 4))
+            .input(2, ScriptEvent::KeyUp(Scancode::A))
+            .run(&mut app)
+            .unwrap();
+
+        let events: Vec<_> = app
+            .0
+            .iter()
+            .map(|(frame, event)| match event {
+                Event::KeyDown { scancode, .. } => (*frame, format!("down {:?}", scancode)),
+                Event::KeyUp { scancode, .. } => (*frame, format!("up {:?}", scancode)),
+                Event::TextInput { text, .. } => (*frame, format!("text {}", text)),
+                Event::MouseMotion { x, y, .. } => (*frame, format!("motion {} {}", x, y)),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                (1, "down Some(A)".to_string()),
+                (1, "motion 3 4".to_string()),
+                (1, "text a".to_string()),
+                (2, "up Some(A)".to_string()),
+            ]
+        );
+    }
+}
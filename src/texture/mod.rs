@@ -1,26 +1,109 @@
-use std::path::Path;
+use std::{cell::RefCell, mem, os::raw::c_int, path::Path, ptr};
 
-use sdl2::render::Texture as SdlTexture;
+use sdl2::{
+    pixels::PixelFormatEnum,
+    render::{BlendMode, Texture as SdlTexture, WindowCanvas},
+    sys,
+};
 
-use crate::{Color, Point, Rect, Surface, TextureValueError, ENGINE, NOT_INIT};
+use crate::{
+    engine::Engine,
+    float::check,
+    transform::{self, TextureCopy},
+    with_engine, with_engine_mut, Color, FPoint, FRect, Point, Rect, Surface, TextureValueError,
+    Transform, ENGINE,
+};
+
+mod target;
+pub use target::{drawing_to_target, with_target};
+pub(crate) use target::{target_set, target_size};
 
 #[cfg(not(feature = "image"))]
 mod error;
 #[cfg(not(feature = "image"))]
 use error::LoadTextureError;
 
+/// Error message for using a texture with an engine other than the one that created it.
+const STALE: &str = "Texture was created by an engine that is no longer running";
+
 /// A (potentially hardware accelerated) renderable texture.
-pub struct Texture(Option<SdlTexture>);
+///
+/// A `Texture` belongs to the engine it was created with, and can't be used once that engine has
+/// been destroyed.
+pub struct Texture {
+    texture: Option<SdlTexture>,
+    /// ID of the engine that created this texture.
+    engine: u64,
+}
 
 impl Texture {
+    fn new(texture: SdlTexture, engine: &Engine) -> Self {
+        Self::from_sdl(texture, engine.id)
+    }
+
+    /// Wrap a texture created by the engine with the ID `engine_id`.
+    pub(crate) fn from_sdl(texture: SdlTexture, engine_id: u64) -> Self {
+        Self {
+            texture: Some(texture),
+            engine: engine_id,
+        }
+    }
+
+    /// Get the underlying texture, checking that it belongs to `engine`.
+    fn get(&self, engine: &Engine) -> Result<&SdlTexture, String> {
+        if engine.id == self.engine {
+            Ok(self.texture.as_ref().unwrap())
+        } else {
+            Err(STALE.to_string())
+        }
+    }
+
+    /// Get the underlying texture mutably, checking that it belongs to the engine with the ID
+    /// `engine_id`.
+    pub(crate) fn get_mut(&mut self, engine_id: u64) -> Result<&mut SdlTexture, String> {
+        if engine_id == self.engine {
+            Ok(self.texture.as_mut().unwrap())
+        } else {
+            Err(STALE.to_string())
+        }
+    }
+
+    /// Check that this texture belongs to the running engine.
+    ///
+    /// # Panics
+    /// Panics if it doesn't, since the underlying texture has already been destroyed.
+    fn check_engine(&self) {
+        let id = with_engine(|e| e.id);
+        assert_eq!(id, self.engine, "{}", STALE);
+    }
+
+    /// Create a new, transparent `Texture` which can be drawn to with
+    /// [`with_target`][crate::with_target].
+    pub fn new_target(width: u32, height: u32) -> Result<Self, String> {
+        let mut texture = with_engine(|engine| {
+            engine
+                .texture_creator
+                .create_texture_target(PixelFormatEnum::ARGB8888, width, height)
+                .map(|t| Self::new(t, engine))
+                .map_err(|e| e.to_string())
+        })?;
+        texture
+            .texture
+            .as_mut()
+            .unwrap()
+            .set_blend_mode(BlendMode::Blend);
+        // New textures contain garbage
+        with_target(&mut texture, || crate::clear(Color::RGBA(0, 0, 0, 0)))?;
+        Ok(texture)
+    }
+
     /// Load a T`exture` from an existing [`Surface`][crate::Surface].
     pub fn from_surface(surface: &Surface) -> Result<Self, TextureValueError> {
-        ENGINE.with(|e| {
-            let engine = e.get().expect(NOT_INIT).borrow();
+        with_engine(|engine| {
             surface
                 .0
                 .as_texture(&engine.texture_creator)
-                .map(|t| Self(Some(t)))
+                .map(|t| Self::new(t, engine))
         })
     }
 
@@ -29,12 +112,11 @@ impl Texture {
     pub fn from_file<P: AsRef<Path>>(file_path: P) -> Result<Self, String> {
         use sdl2::image::LoadTexture;
 
-        ENGINE.with(move |e| {
-            let engine = e.get().expect(NOT_INIT).borrow();
+        with_engine(|engine| {
             engine
                 .texture_creator
                 .load_texture(file_path)
-                .map(|t| Self(Some(t)))
+                .map(|t| Self::new(t, engine))
         })
     }
 
@@ -45,12 +127,11 @@ impl Texture {
         use LoadTextureError as E;
         let surface = Surface::load_bmp(file_path).map_err(|e| E::LoadError(e))?;
 
-        ENGINE.with(move |e| {
-            let mut engine = e.get().expect(NOT_INIT).borrow_mut();
+        with_engine(|engine| {
             surface
-                .as_texture(&mut engine.texture_creator)
-                .map(|t| Self(Some(t)))
-                .map_err(|e| E::ValueError(e))
+                .as_texture(&engine.texture_creator)
+                .map(|t| Self::new(t, engine))
+                .map_err(E::ValueError)
         })
     }
 
@@ -62,11 +143,23 @@ impl Texture {
         R1: Into<Option<Rect>>,
         R2: Into<Option<Rect>>,
     {
-        let texture = self.0.as_ref().unwrap();
-
-        ENGINE.with(|e| {
-            let mut engine = e.get().expect(NOT_INIT).borrow_mut();
-            engine.canvas.copy(texture, src, dst)
+        with_engine_mut(|engine| {
+            let t = transform::current(engine);
+            let texture = self.get(engine)?;
+            match dst.into() {
+                Some(dst) if t != Transform::IDENTITY => {
+                    let copy = TextureCopy {
+                        dst: dst.into(),
+                        angle: 0.0,
+                        center: None,
+                        flip_horizontal: false,
+                        flip_vertical: false,
+                    };
+                    copy_f(&engine.canvas, texture, src.into(), t.copy(copy))
+                }
+                // Filling the canvas ignores the transform
+                dst => engine.canvas.copy(texture, src, dst),
+            }
         })
     }
 
@@ -88,31 +181,104 @@ impl Texture {
         R2: Into<Option<Rect>>,
         P: Into<Option<Point>>,
     {
-        let texture = self.0.as_ref().unwrap();
-
-        ENGINE.with(|e| {
-            let mut engine = e.get().expect(NOT_INIT).borrow_mut();
-            engine.canvas.copy_ex(
-                texture,
-                src,
-                dst,
-                angle,
-                center,
-                flip_horizontal,
-                flip_vertical,
-            )
+        with_engine_mut(|engine| {
+            let t = transform::current(engine);
+            let texture = self.get(engine)?;
+            let center = center.into();
+            match dst.into() {
+                Some(dst) if t != Transform::IDENTITY => {
+                    let copy = TextureCopy {
+                        dst: dst.into(),
+                        angle,
+                        center: center.map(FPoint::from),
+                        flip_horizontal,
+                        flip_vertical,
+                    };
+                    copy_f(&engine.canvas, texture, src.into(), t.copy(copy))
+                }
+                // Filling the canvas ignores the transform
+                dst => engine.canvas.copy_ex(
+                    texture,
+                    src,
+                    dst,
+                    angle,
+                    center,
+                    flip_horizontal,
+                    flip_vertical,
+                ),
+            }
+        })
+    }
+
+    /// Draw the portion of a `Texture` given by `src` to `dst` on the canvas, where `dst` can be
+    /// between pixels. This keeps slowly moving textures moving smoothly. If `src` is `None`, draws
+    /// the entire `Texture`. If `dst` is `None`, draws the `Texture` to fill the entire canvas.
+    pub fn draw_f<R1, R2>(&self, src: R1, dst: R2) -> Result<(), String>
+    where
+        R1: Into<Option<Rect>>,
+        R2: Into<Option<FRect>>,
+    {
+        self.draw_ex_f(src, dst, 0.0, None, false, false)
+    }
+
+    /// Draw the portion of a `Texture` given by `src` to `dst` on the canvas, rotated around
+    /// `center` by `angle`, and optionally flipped horizontally or vertically. `dst` and `center`
+    /// can be between pixels. If `src` is `None`, draws the entire `Texture`. If `dst` is `None`,
+    /// draws the `Texture` to fill the entire canvas.
+    pub fn draw_ex_f<R1, R2, P>(
+        &self,
+        src: R1,
+        dst: R2,
+        angle: f64,
+        center: P,
+        flip_horizontal: bool,
+        flip_vertical: bool,
+    ) -> Result<(), String>
+    where
+        R1: Into<Option<Rect>>,
+        R2: Into<Option<FRect>>,
+        P: Into<Option<FPoint>>,
+    {
+        with_engine_mut(|engine| {
+            let t = transform::current(engine);
+            let texture = self.get(engine)?;
+            let center = center.into();
+            match dst.into() {
+                Some(dst) => {
+                    let copy = TextureCopy {
+                        dst,
+                        angle,
+                        center,
+                        flip_horizontal,
+                        flip_vertical,
+                    };
+                    copy_f(&engine.canvas, texture, src.into(), t.copy(copy))
+                }
+                // Filling the canvas ignores the transform
+                None => engine.canvas.copy_ex(
+                    texture,
+                    src,
+                    None,
+                    angle,
+                    center.map(|c| Point::new(c.x.round() as i32, c.y.round() as i32)),
+                    flip_horizontal,
+                    flip_vertical,
+                ),
+            }
         })
     }
 
     /// Returns the size of the `Texture` in pixels.
     pub fn size(&self) -> Point {
-        let query = self.0.as_ref().unwrap().query();
+        self.check_engine();
+        let query = self.texture.as_ref().unwrap().query();
         Point::new(query.width as i32, query.height as i32)
     }
 
     /// Returns a [`Color`][crate::Color] representing the color and alpha mod of the `Texture`.
     pub fn mod_(&self) -> Color {
-        let texture = self.0.as_ref().unwrap();
+        self.check_engine();
+        let texture = self.texture.as_ref().unwrap();
         let (r, g, b) = texture.color_mod();
         let a = texture.alpha_mod();
         Color::RGBA(r, g, b, a)
@@ -120,26 +286,105 @@ impl Texture {
 
     /// Sets the color and alpha mod of the `Texture`.
     pub fn set_mod<C: Into<Color>>(&mut self, mod_: C) {
+        self.check_engine();
         let (r, g, b, a) = mod_.into().rgba();
-        let texture = self.0.as_mut().unwrap();
+        let texture = self.texture.as_mut().unwrap();
         texture.set_color_mod(r, g, b);
         texture.set_alpha_mod(a);
     }
 }
 
+extern "C" {
+    // The flip parameter of `sys::SDL_RenderCopyExF` is an enum, which can't hold both flags at
+    // once, but SDL treats it as a bitmask.
+    #[link_name = "SDL_RenderCopyExF"]
+    fn render_copy_ex_f(
+        renderer: *mut sys::SDL_Renderer,
+        texture: *mut sys::SDL_Texture,
+        srcrect: *const sys::SDL_Rect,
+        dstrect: *const sys::SDL_FRect,
+        angle: f64,
+        center: *const sys::SDL_FPoint,
+        flip: u32,
+    ) -> c_int;
+}
+
+/// Copy a texture to the canvas, using SDL's floating point rendering.
+fn copy_f(
+    canvas: &WindowCanvas,
+    texture: &SdlTexture,
+    src: Option<Rect>,
+    copy: TextureCopy,
+) -> Result<(), String> {
+    let src = src.as_ref().map_or(ptr::null(), |r| r.raw());
+    if copy.angle == 0.0 && !copy.flip_horizontal && !copy.flip_vertical {
+        // Safety: The renderer is valid for as long as the engine exists, and the texture was
+        // created by it.
+        return check(unsafe {
+            sys::SDL_RenderCopyF(canvas.raw(), texture.raw(), src, &copy.dst.raw())
+        });
+    }
+
+    let mut flip = sys::SDL_RendererFlip::SDL_FLIP_NONE as u32;
+    if copy.flip_horizontal {
+        flip |= sys::SDL_RendererFlip::SDL_FLIP_HORIZONTAL as u32;
+    }
+    if copy.flip_vertical {
+        flip |= sys::SDL_RendererFlip::SDL_FLIP_VERTICAL as u32;
+    }
+    let center = copy.center.map(FPoint::raw);
+    let center = center.as_ref().map_or(ptr::null(), |c| c as *const _);
+    // Safety: As above.
+    check(unsafe {
+        render_copy_ex_f(
+            canvas.raw(),
+            texture.raw(),
+            src,
+            &copy.dst.raw(),
+            copy.angle,
+            center,
+            flip,
+        )
+    })
+}
+
 impl Drop for Texture {
     fn drop(&mut self) {
-        ENGINE.with(|e| {
-            if e.get().is_some() {
-                // Safety: TextureCreator definitely exists and is the same object, so this is safe.
-                unsafe {
-                    self.0.take().unwrap().destroy();
+        let texture = self.texture.take().unwrap();
+        ENGINE.with(|e| match e.try_borrow() {
+            Ok(engine) => {
+                if engine.as_ref().is_some_and(|e| e.id == self.engine) {
+                    // Safety: TextureCreator definitely exists and is the same object, so this is
+                    // safe.
+                    unsafe { texture.destroy() }
                 }
+                // Otherwise, the texture was destroyed along with its engine
             }
+            // Dropped inside `with_engine`, so destroy it once the engine is free
+            Err(_) => DROPPED.with(|d| d.borrow_mut().push((self.engine, texture))),
         })
     }
 }
 
+thread_local! {
+    /// Textures that were dropped while the engine was borrowed, with the ID of the engine that
+    /// created them.
+    static DROPPED: RefCell<Vec<(u64, SdlTexture)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Destroy the textures that were dropped while the engine was borrowed. Called internally by the
+/// engine every frame, and before it is destroyed.
+pub(crate) fn destroy_dropped(engine: &Engine) {
+    let dropped = DROPPED.with(|d| mem::take(&mut *d.borrow_mut()));
+    for (id, texture) in dropped {
+        if id == engine.id {
+            // Safety: As in `Texture::drop`.
+            unsafe { texture.destroy() }
+        }
+        // Otherwise, the texture was destroyed along with its engine
+    }
+}
+
 /// Draw the portion of a `Texture` given by `src` to `dst` on the canvas. If `src` is `None`,
 /// draws the entire `Texture`. If `dst` is `None`, draws the `Texture` to fill the entire
 /// canvas.
@@ -171,3 +416,159 @@ where
 {
     texture.draw_ex(src, dst, angle, center, flip_horizontal, flip_vertical)
 }
+
+/// Draw the portion of a `Texture` given by `src` to `dst` on the canvas, where `dst` can be
+/// between pixels. If `src` is `None`, draws the entire `Texture`. If `dst` is `None`, draws the
+/// `Texture` to fill the entire canvas.
+pub fn draw_texture_f<R1, R2>(texture: &Texture, src: R1, dst: R2) -> Result<(), String>
+where
+    R1: Into<Option<Rect>>,
+    R2: Into<Option<FRect>>,
+{
+    texture.draw_f(src, dst)
+}
+
+/// Draw the portion of a `Texture` given by `src` to `dst` on the canvas, rotated around
+/// `center` by `angle`, and optionally flipped horizontally or vertically. `dst` and `center` can
+/// be between pixels. If `src` is `None`, draws the entire `Texture`. If `dst` is `None`, draws
+/// the `Texture` to fill the entire canvas.
+pub fn draw_texture_ex_f<R1, R2, P>(
+    texture: &Texture,
+    src: R1,
+    dst: R2,
+    angle: f64,
+    center: P,
+    flip_horizontal: bool,
+    flip_vertical: bool,
+) -> Result<(), String>
+where
+    R1: Into<Option<Rect>>,
+    R2: Into<Option<FRect>>,
+    P: Into<Option<FPoint>>,
+{
+    texture.draw_ex_f(src, dst, angle, center, flip_horizontal, flip_vertical)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+
+    /// Create a 2x2 texture with a different color in each pixel.
+    fn quadrants() -> Result<Texture, String> {
+        let mut texture = Texture::new_target(2, 2)?;
+        with_target(&mut texture, || {
+            crate::draw_point(Point::new(0, 0), Color::RED)?;
+            crate::draw_point(Point::new(1, 0), Color::GREEN)?;
+            crate::draw_point(Point::new(0, 1), Color::BLUE)?;
+            crate::draw_point(Point::new(1, 1), Color::WHITE)
+        })??;
+        Ok(texture)
+    }
+
+    /// Check the colors at the centers of the quadrants of the 8x8 area at (`x`, `y`).
+    fn assert_quadrants(shot: &Surface, x: u32, y: u32, colors: [Color; 4]) {
+        assert_eq!(shot.pixel(x + 2, y + 2), Some(colors[0]));
+        assert_eq!(shot.pixel(x + 6, y + 2), Some(colors[1]));
+        assert_eq!(shot.pixel(x + 2, y + 6), Some(colors[2]));
+        assert_eq!(shot.pixel(x + 6, y + 6), Some(colors[3]));
+    }
+
+    /// Textures dropped while the engine is borrowed are destroyed on the next frame.
+    #[test]
+    fn test_drop_while_borrowed() {
+        let dropped = || DROPPED.with(|d| d.borrow().len());
+        test_util::run(|| {
+            let mut frame = 0;
+            let mut app = |_| {
+                if frame == 0 {
+                    let texture = Texture::new_target(2, 2)?;
+                    with_engine_mut(|_| drop(texture));
+                    assert_eq!(dropped(), 1);
+                    with_engine(destroy_dropped);
+                    assert_eq!(dropped(), 0);
+
+                    // Left for the engine to destroy before the next frame
+                    let texture = Texture::new_target(2, 2)?;
+                    with_engine_mut(|_| drop(texture));
+                    assert_eq!(dropped(), 1);
+                } else {
+                    assert_eq!(dropped(), 0);
+                }
+                frame += 1;
+                Ok(true)
+            };
+            crate::Builder::new("Test", 8, 8)
+                .headless(true)
+                .max_frames(2)
+                .start(&mut app)
+                .unwrap();
+        });
+    }
+
+    /// Floating point destinations are transformed like integer ones.
+    #[test]
+    fn test_draw_f() {
+        test_util::check_frame(
+            16,
+            16,
+            || {
+                crate::clear(Color::BLACK);
+                let texture = quadrants()?;
+                crate::push_transform(Transform::scaling(2.0, 2.0));
+                texture.draw_f(None, FRect::new(2.0, 2.0, 4.0, 4.0))
+            },
+            |shot| {
+                assert_eq!(shot.pixel(2, 2), Some(Color::BLACK));
+                let colors = [Color::RED, Color::GREEN, Color::BLUE, Color::WHITE];
+                assert_quadrants(shot, 4, 4, colors);
+            },
+        );
+    }
+
+    /// Textures are rotated clockwise around their center by default.
+    #[test]
+    fn test_draw_ex_f() {
+        test_util::check_frame(
+            8,
+            8,
+            || {
+                crate::clear(Color::BLACK);
+                let texture = quadrants()?;
+                let dst = FRect::new(0.0, 0.0, 8.0, 8.0);
+                texture.draw_ex_f(None, dst, 90.0, None, false, false)
+            },
+            |shot| {
+                let colors = [Color::BLUE, Color::RED, Color::WHITE, Color::GREEN];
+                assert_quadrants(shot, 0, 0, colors);
+            },
+        );
+    }
+
+    /// Textures can be flipped either way, or both ways at once.
+    #[test]
+    fn test_copy_f_flips() {
+        test_util::check_frame(
+            16,
+            16,
+            || {
+                crate::clear(Color::BLACK);
+                let texture = quadrants()?;
+                for &(x, y, h, v) in &[(0, 0, false, true), (8, 0, true, false), (8, 8, true, true)]
+                {
+                    let dst = FRect::new(x as f32, y as f32, 8.0, 8.0);
+                    texture.draw_ex_f(None, dst, 0.0, None, h, v)?;
+                }
+                Ok(())
+            },
+            |shot| {
+                let colors = [Color::BLUE, Color::WHITE, Color::RED, Color::GREEN];
+                assert_quadrants(shot, 0, 0, colors);
+                let colors = [Color::GREEN, Color::RED, Color::WHITE, Color::BLUE];
+                assert_quadrants(shot, 8, 0, colors);
+                let colors = [Color::WHITE, Color::BLUE, Color::GREEN, Color::RED];
+                assert_quadrants(shot, 8, 8, colors);
+            },
+        );
+    }
+}
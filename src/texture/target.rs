@@ -0,0 +1,242 @@
+use std::ptr;
+
+use sdl2::{render::TextureAccess, sys};
+
+use super::Texture;
+use crate::{engine::Engine, with_engine, with_engine_mut, Rect, ENGINE};
+
+/// The state of the canvas which SDL resets when the render target changes.
+struct TargetState {
+    target: *mut sys::SDL_Texture,
+    viewport: Rect,
+    clip: Option<Rect>,
+    scale: (f32, f32),
+}
+
+impl TargetState {
+    fn save(engine: &Engine) -> Self {
+        let canvas = &engine.canvas;
+        Self {
+            // Safety: The renderer is valid for as long as the engine exists.
+            target: unsafe { sys::SDL_GetRenderTarget(canvas.raw()) },
+            viewport: canvas.viewport(),
+            clip: canvas.clip_rect(),
+            scale: canvas.scale(),
+        }
+    }
+
+    fn restore(&self, engine: &mut Engine) -> Result<(), String> {
+        set_target(engine, self.target)?;
+        let canvas = &mut engine.canvas;
+        canvas.set_scale(self.scale.0, self.scale.1)?;
+        canvas.set_viewport(self.viewport);
+        canvas.set_clip_rect(self.clip);
+        Ok(())
+    }
+}
+
+fn set_target(engine: &mut Engine, target: *mut sys::SDL_Texture) -> Result<(), String> {
+    // Safety: The renderer is valid for as long as the engine exists, and the target is either
+    // null (the window) or a texture created by it.
+    if unsafe { sys::SDL_SetRenderTarget(engine.canvas.raw(), target) } == 0 {
+        Ok(())
+    } else {
+        Err(sdl2::get_error())
+    }
+}
+
+/// Run a function with all drawing directed to `target` instead of the canvas, then restore the
+/// previous target. The target must have been created with [`Texture::new_target`].
+///
+/// Calls can be nested. While drawing to the target, the viewport covers the whole texture and
+/// there is no scale or clipping rectangle. These are restored afterwards.
+///
+/// This is useful for drawing static parts of the screen, such as backgrounds and UI panels, once
+/// rather than every frame.
+pub fn with_target<F, R>(target: &mut Texture, f: F) -> Result<R, String>
+where
+    F: FnOnce() -> R,
+{
+    let saved = with_engine_mut(|engine| {
+        let texture = target.get(engine)?;
+        if texture.query().access != TextureAccess::Target {
+            return Err("The texture wasn't created as a render target".to_string());
+        }
+        let saved = TargetState::save(engine);
+        set_target(engine, texture.raw())?;
+        Ok(saved)
+    })?;
+
+    let restore = RestoreTarget(Some(saved));
+    let res = f();
+    restore.finish()?;
+    Ok(res)
+}
+
+/// Restores the state saved by [`with_target`] when dropped, so that it's restored even if
+/// drawing to the target panics.
+struct RestoreTarget(Option<TargetState>);
+
+impl RestoreTarget {
+    fn finish(mut self) -> Result<(), String> {
+        let saved = self.0.take().unwrap();
+        with_engine_mut(|engine| saved.restore(engine))
+    }
+}
+
+impl Drop for RestoreTarget {
+    fn drop(&mut self) {
+        let saved = match self.0.take() {
+            Some(saved) => saved,
+            None => return,
+        };
+        ENGINE.with(|e| {
+            if let Ok(mut engine) = e.try_borrow_mut() {
+                if let Some(engine) = engine.as_mut() {
+                    // This fails silently on error, as the thread is already panicking
+                    saved.restore(engine).ok();
+                }
+            }
+        })
+    }
+}
+
+/// Returns whether drawing is currently directed to a texture by [`with_target`].
+pub fn drawing_to_target() -> bool {
+    with_engine(target_set)
+}
+
+/// Get the size of the texture the engine's canvas is drawing to, or `None` if it is drawing to
+/// the window.
+pub(crate) fn target_size(engine: &Engine) -> Option<(u32, u32)> {
+    let (mut width, mut height) = (0, 0);
+    // Safety: The renderer is valid for as long as the engine exists, and any target is a texture
+    // created by it.
+    let queried = unsafe {
+        let target = sys::SDL_GetRenderTarget(engine.canvas.raw());
+        !target.is_null()
+            && sys::SDL_QueryTexture(
+                target,
+                ptr::null_mut(),
+                ptr::null_mut(),
+                &mut width,
+                &mut height,
+            ) == 0
+    };
+    if queried {
+        Some((width as u32, height as u32))
+    } else {
+        None
+    }
+}
+
+/// Returns whether the engine's canvas is drawing to a texture rather than the window.
+pub(crate) fn target_set(engine: &Engine) -> bool {
+    // Safety: The renderer is valid for as long as the engine exists.
+    unsafe { !sys::SDL_GetRenderTarget(engine.canvas.raw()).is_null() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_util, Color};
+
+    /// The camera is centered on the target while drawing to one.
+    #[test]
+    fn test_target_camera() {
+        test_util::check_frame(
+            16,
+            16,
+            || {
+                crate::clear(Color::BLACK);
+                let mut texture = Texture::new_target(8, 8)?;
+                crate::set_camera(crate::Camera::new(0.0, 0.0));
+                let drawn = with_target(&mut texture, || {
+                    crate::fill_rect(Rect::new(-1, -1, 2, 2), Color::RED)
+                });
+                crate::set_camera(None);
+                drawn??;
+                texture.draw(None, Rect::new(0, 0, 8, 8))
+            },
+            |shot| {
+                assert_eq!(shot.pixel(3, 3), Some(Color::RED));
+                assert_eq!(shot.pixel(4, 4), Some(Color::RED));
+                assert_eq!(shot.pixel(7, 7), Some(Color::BLACK));
+            },
+        );
+    }
+
+    /// Drawing to a target texture doesn't affect the canvas until the texture is drawn.
+    #[test]
+    fn test_with_target() {
+        test_util::check_frame(
+            8,
+            8,
+            || {
+                crate::clear(Color::BLUE);
+                let mut target = Texture::new_target(4, 4)?;
+                with_target(&mut target, || {
+                    assert!(drawing_to_target());
+                    crate::fill_rect(Rect::new(0, 0, 2, 4), Color::RED)
+                })??;
+                assert!(!drawing_to_target());
+                target.draw(None, Rect::new(4, 4, 4, 4))
+            },
+            |shot| {
+                assert_eq!(shot.pixel(4, 4), Some(Color::RED));
+                // The rest of the texture is transparent
+                assert_eq!(shot.pixel(7, 7), Some(Color::BLUE));
+                assert_eq!(shot.pixel(0, 0), Some(Color::BLUE));
+            },
+        );
+    }
+
+    /// The state is restored even if drawing to the target panics.
+    #[test]
+    fn test_with_target_panic() {
+        test_util::check_frame(
+            8,
+            8,
+            || {
+                crate::clear(Color::BLACK);
+                crate::set_clip_rect(Rect::new(0, 0, 4, 4));
+                let mut target = Texture::new_target(4, 4)?;
+                let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                    with_target(&mut target, || panic!("Drawing failed"))
+                }));
+                assert!(res.is_err());
+                assert!(!drawing_to_target());
+                assert_eq!(crate::clip_rect(), Some(Rect::new(0, 0, 4, 4)));
+                crate::fill_rect(Rect::new(0, 0, 8, 8), Color::RED)
+            },
+            |shot| {
+                assert_eq!(shot.pixel(0, 0), Some(Color::RED));
+                assert_eq!(shot.pixel(7, 7), Some(Color::BLACK));
+            },
+        );
+    }
+
+    /// The viewport of a target isn't offset by the logical area of the window.
+    #[test]
+    fn test_target_viewport() {
+        test_util::run(|| {
+            let mut app = |_| {
+                let mut target = Texture::new_target(4, 4)?;
+                with_target(&mut target, || {
+                    crate::set_viewport(Rect::new(1, 1, 2, 2));
+                    assert_eq!(crate::viewport(), Rect::new(1, 1, 2, 2));
+                    assert_eq!(with_engine(|e| e.canvas.viewport()), Rect::new(1, 1, 2, 2));
+                })?;
+                crate::set_viewport(None);
+                assert_eq!(crate::viewport(), Rect::new(0, 0, 4, 4));
+                Ok(true)
+            };
+            crate::Builder::new("Test", 8, 4)
+                .logical_size(4, 4, crate::ScaleMode::Letterbox)
+                .headless(true)
+                .max_frames(1)
+                .start(&mut app)
+                .unwrap();
+        });
+    }
+}
@@ -0,0 +1,82 @@
+use crate::with_engine;
+
+/// Returns how far (from 0.0 to 1.0) the current frame is between the last fixed update and the
+/// next one. This can be used in [`Application::on_update`][crate::Application::on_update] to
+/// interpolate between the previous and current state of objects updated in
+/// [`Application::on_fixed_update`][crate::Application::on_fixed_update], for smooth rendering.
+///
+/// Always returns 1.0 if a fixed timestep isn't in use.
+pub fn interpolation_alpha() -> f64 {
+    with_engine(|e| e.timestep.as_ref().map_or(1.0, FixedTimestep::alpha))
+}
+
+/// Divides variable frame times into fixed-size steps, carrying the remainder over to the next
+/// frame.
+pub(crate) struct FixedTimestep {
+    step: f64,
+    max_steps: u32,
+    acc: f64,
+}
+
+impl FixedTimestep {
+    /// Create a timestep running `hz` steps per second, and at most `max_steps` steps per frame.
+    pub fn new(hz: f64, max_steps: u32) -> Self {
+        Self {
+            step: hz.recip(),
+            max_steps,
+            acc: 0.0,
+        }
+    }
+
+    /// The length of a single step, in seconds.
+    #[inline]
+    pub fn step(&self) -> f64 {
+        self.step
+    }
+
+    /// Add the elapsed time of a frame, returning the number of steps to run for it.
+    ///
+    /// If more than `max_steps` steps are due, the time that can't be caught up on is dropped, so
+    /// a slow frame doesn't cause ever more steps to be run.
+    pub fn advance(&mut self, elapsed_time: f64) -> u32 {
+        self.acc += elapsed_time;
+        let mut steps = 0;
+        while self.acc >= self.step && steps < self.max_steps {
+            self.acc -= self.step;
+            steps += 1;
+        }
+        if self.acc >= self.step {
+            self.acc %= self.step;
+        }
+        steps
+    }
+
+    /// How far the accumulated time is into the next step.
+    pub fn alpha(&self) -> f64 {
+        self.acc / self.step
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Time is divided into steps, with the remainder carried over.
+    #[test]
+    fn test_advance() {
+        let mut ts = FixedTimestep::new(10.0, 5);
+        assert_eq!(ts.advance(0.05), 0);
+        assert!((ts.alpha() - 0.5).abs() < 1e-9);
+        assert_eq!(ts.advance(0.2), 2);
+        assert!((ts.alpha() - 0.5).abs() < 1e-9);
+    }
+
+    /// No more than `max_steps` steps are run, and the excess time is dropped.
+    #[test]
+    fn test_max_steps() {
+        let mut ts = FixedTimestep::new(10.0, 3);
+        assert_eq!(ts.advance(1.05), 3);
+        assert!(ts.alpha() < 1.0);
+        assert_eq!(ts.advance(0.0), 0);
+    }
+}
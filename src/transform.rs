@@ -0,0 +1,502 @@
+use sdl2::render::WindowCanvas;
+
+use crate::{engine::Engine, texture, with_engine, with_engine_mut, FPoint, FRect, Point, Rect};
+
+/// A 2D affine transform, such as a translation, scale or rotation, or any combination of them.
+///
+/// Transforms are combined with [`then`](Self::then), so
+/// `Transform::scaling(2.0, 2.0).then(Transform::translation(10.0, 0.0))` doubles the size of
+/// everything drawn, then moves it 10 pixels to the right.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    // A point (x, y) is transformed to (a * x + c * y + tx, b * x + d * y + ty)
+    a: f32,
+    b: f32,
+    c: f32,
+    d: f32,
+    tx: f32,
+    ty: f32,
+}
+
+impl Transform {
+    /// The transform which leaves everything unchanged.
+    pub const IDENTITY: Self = Self {
+        a: 1.0,
+        b: 0.0,
+        c: 0.0,
+        d: 1.0,
+        tx: 0.0,
+        ty: 0.0,
+    };
+
+    /// Create a transform which moves everything by (`x`, `y`).
+    pub fn translation(x: f32, y: f32) -> Self {
+        Self {
+            tx: x,
+            ty: y,
+            ..Self::IDENTITY
+        }
+    }
+
+    /// Create a transform which scales everything by `x` horizontally and `y` vertically, around
+    /// the origin.
+    pub fn scaling(x: f32, y: f32) -> Self {
+        Self {
+            a: x,
+            d: y,
+            ..Self::IDENTITY
+        }
+    }
+
+    /// Create a transform which rotates everything clockwise by `degrees` around the origin.
+    pub fn rotation(degrees: f32) -> Self {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        Self {
+            a: cos,
+            b: sin,
+            c: -sin,
+            d: cos,
+            ..Self::IDENTITY
+        }
+    }
+
+    /// Combine this transform with another, which is applied after this one.
+    pub fn then(self, next: Transform) -> Self {
+        Self {
+            a: next.a * self.a + next.c * self.b,
+            b: next.b * self.a + next.d * self.b,
+            c: next.a * self.c + next.c * self.d,
+            d: next.b * self.c + next.d * self.d,
+            tx: next.a * self.tx + next.c * self.ty + next.tx,
+            ty: next.b * self.tx + next.d * self.ty + next.ty,
+        }
+    }
+
+    /// Get the transform which undoes this one, or `None` if it can't be undone, such as a
+    /// scale of 0.
+    pub fn inverse(self) -> Option<Self> {
+        let det = self.a * self.d - self.b * self.c;
+        if det == 0.0 || !det.is_finite() {
+            return None;
+        }
+        let (a, b, c, d) = (self.d / det, -self.b / det, -self.c / det, self.a / det);
+        Some(Self {
+            a,
+            b,
+            c,
+            d,
+            tx: -(a * self.tx + c * self.ty),
+            ty: -(b * self.tx + d * self.ty),
+        })
+    }
+
+    /// Transform the point (`x`, `y`).
+    pub fn apply(self, x: f32, y: f32) -> (f32, f32) {
+        (
+            self.a * x + self.c * y + self.tx,
+            self.b * x + self.d * y + self.ty,
+        )
+    }
+
+    /// Transform a point, rounding the result to the nearest pixel.
+    pub fn apply_point(self, p: Point) -> Point {
+        if self == Self::IDENTITY {
+            return p;
+        }
+        let (x, y) = self.apply(p.x() as f32, p.y() as f32);
+        Point::new(x.round() as i32, y.round() as i32)
+    }
+
+    /// Returns whether this transform keeps horizontal lines horizontal, so rectangles stay
+    /// rectangles.
+    pub(crate) fn is_axis_aligned(self) -> bool {
+        self.b == 0.0 && self.c == 0.0
+    }
+
+    /// Get the horizontal and vertical scale, ignoring any rotation. The vertical scale is
+    /// negative if the transform mirrors what is drawn.
+    pub(crate) fn scale(self) -> (f32, f32) {
+        let x = self.a.hypot(self.b);
+        let det = self.a * self.d - self.b * self.c;
+        (x, if x == 0.0 { 0.0 } else { det / x })
+    }
+
+    /// Get the clockwise rotation in degrees.
+    pub(crate) fn angle(self) -> f32 {
+        self.b.atan2(self.a).to_degrees()
+    }
+
+    /// Transform a rectangle, or return `None` if the result isn't an axis-aligned rectangle.
+    pub(crate) fn rect(self, rect: Rect) -> Option<Rect> {
+        if self == Self::IDENTITY {
+            return Some(rect);
+        }
+        let rect = self.frect(rect.into())?;
+        let (left, top) = (rect.x.round(), rect.y.round());
+        let (right, bottom) = (
+            (rect.x + rect.width).round(),
+            (rect.y + rect.height).round(),
+        );
+        Some(Rect::new(
+            left as i32,
+            top as i32,
+            (right - left) as u32,
+            (bottom - top) as u32,
+        ))
+    }
+
+    /// Transform a floating point rectangle, or return `None` if the result isn't an
+    /// axis-aligned rectangle.
+    pub(crate) fn frect(self, rect: FRect) -> Option<FRect> {
+        if !self.is_axis_aligned() {
+            return None;
+        }
+        let [a, _, c, _] = self.corners(rect);
+        let (left, top) = (a.0.min(c.0), a.1.min(c.1));
+        let (right, bottom) = (a.0.max(c.0), a.1.max(c.1));
+        Some(FRect::new(left, top, right - left, bottom - top))
+    }
+
+    /// Transform the corners of a rectangle, clockwise from the top left.
+    pub(crate) fn corners(self, rect: FRect) -> [(f32, f32); 4] {
+        let (left, top) = (rect.x, rect.y);
+        let (right, bottom) = (rect.x + rect.width, rect.y + rect.height);
+        [
+            self.apply(left, top),
+            self.apply(right, top),
+            self.apply(right, bottom),
+            self.apply(left, bottom),
+        ]
+    }
+
+    /// Transform the parameters of a texture copy.
+    ///
+    /// Textures can't be sheared, so a non-uniform scale combined with a rotation is only
+    /// approximated.
+    pub(crate) fn copy(self, copy: TextureCopy) -> TextureCopy {
+        let TextureCopy {
+            dst,
+            angle,
+            flip_horizontal,
+            flip_vertical,
+            ..
+        } = copy;
+        let center = copy.center();
+        let (pivot_x, pivot_y) = self.apply(dst.x + center.x, dst.y + center.y);
+
+        let (scale_x, scale_y) = self.scale();
+        // A mirrored transform flips the texture vertically, which also reverses its rotation
+        // and moves the pivot to the other side of the texture
+        let mirrored = scale_y < 0.0;
+        let (angle, center_y) = if mirrored {
+            (self.angle() as f64 - angle, dst.height - center.y)
+        } else {
+            (self.angle() as f64 + angle, center.y)
+        };
+        let scale_y = scale_y.abs();
+
+        let center = FPoint::new(center.x * scale_x, center_y * scale_y);
+        TextureCopy {
+            dst: FRect::new(
+                pivot_x - center.x,
+                pivot_y - center.y,
+                dst.width * scale_x,
+                dst.height * scale_y,
+            ),
+            angle,
+            center: Some(center),
+            flip_horizontal,
+            flip_vertical: flip_vertical != mirrored,
+        }
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+/// Where a texture is copied to on the canvas, as passed to
+/// [`Texture::draw_ex_f`][crate::Texture::draw_ex_f].
+#[derive(Clone, Copy)]
+pub(crate) struct TextureCopy {
+    pub dst: FRect,
+    pub angle: f64,
+    pub center: Option<FPoint>,
+    pub flip_horizontal: bool,
+    pub flip_vertical: bool,
+}
+
+impl TextureCopy {
+    /// Get the point the texture is rotated around, relative to `dst`.
+    pub fn center(&self) -> FPoint {
+        self.center
+            .unwrap_or_else(|| FPoint::new(self.dst.width / 2.0, self.dst.height / 2.0))
+    }
+}
+
+/// A 2D camera, which determines which part of the world is shown on the screen.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera {
+    /// The point in the world shown at the center of the drawing area.
+    pub position: (f32, f32),
+    /// How much the world is magnified. A zoom of 2 makes everything twice as large.
+    pub zoom: f32,
+    /// How far the camera is rotated clockwise, in degrees. The world appears to rotate the
+    /// other way.
+    pub rotation: f32,
+}
+
+impl Camera {
+    /// Create a camera centered on (`x`, `y`), with no zoom or rotation.
+    pub fn new(x: f32, y: f32) -> Self {
+        Self {
+            position: (x, y),
+            zoom: 1.0,
+            rotation: 0.0,
+        }
+    }
+
+    /// Get the transform from world coordinates to the coordinates of a drawing area of the given
+    /// size.
+    pub fn transform(&self, (width, height): (u32, u32)) -> Transform {
+        Transform::translation(-self.position.0, -self.position.1)
+            .then(Transform::rotation(-self.rotation))
+            .then(Transform::scaling(self.zoom, self.zoom))
+            .then(Transform::translation(
+                width as f32 / 2.0,
+                height as f32 / 2.0,
+            ))
+    }
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self::new(0.0, 0.0)
+    }
+}
+
+/// The camera and transform stack.
+#[derive(Default)]
+pub(crate) struct Transforms {
+    camera: Option<Camera>,
+    /// The combined transform of everything pushed so far, for each push.
+    stack: Vec<Transform>,
+}
+
+impl Transforms {
+    /// Empty the transform stack. Called internally by the engine at the end of every frame.
+    pub fn reset(&mut self) {
+        self.stack.clear();
+    }
+}
+
+/// Get the transform from world coordinates to drawing coordinates.
+pub(crate) fn current(engine: &Engine) -> Transform {
+    let transforms = &engine.transforms;
+    let stack = transforms.stack.last().copied().unwrap_or_default();
+    match transforms.camera {
+        Some(camera) => {
+            // This fails silently on error, centering the camera at the top left
+            let size = match texture::target_size(engine) {
+                Some(size) => size,
+                None => engine.drawing_size().unwrap_or_default(),
+            };
+            stack.then(camera.transform(size))
+        }
+        None => stack,
+    }
+}
+
+/// Set or clear the camera. While a camera is set, everything is drawn in world coordinates,
+/// which the camera maps to the drawing area. While drawing to a texture with
+/// [`with_target`][crate::with_target], the camera is centered on the texture instead.
+///
+/// The camera and [transform stack][push_transform] apply to every drawing function and texture
+/// draw, including drawing to a texture with [`with_target`][crate::with_target]. They don't
+/// affect [`clear`][crate::clear], the viewport or the clipping rectangle, which are always in
+/// drawing coordinates, and textures drawn with a `dst` of `None` still fill the canvas.
+pub fn set_camera<C: Into<Option<Camera>>>(camera: C) {
+    with_engine_mut(|engine| engine.transforms.camera = camera.into())
+}
+
+/// Get the camera currently in use (if any).
+pub fn camera() -> Option<Camera> {
+    with_engine(|engine| engine.transforms.camera)
+}
+
+/// Push a transform onto the transform stack. Until it is popped with [`pop_transform`], it is
+/// applied to everything drawn, before any transforms already on the stack and the camera.
+///
+/// This is useful for drawing a group of shapes relative to an object, such as a rotating ship.
+/// The stack is emptied at the end of every frame.
+pub fn push_transform(transform: Transform) {
+    with_engine_mut(|engine| {
+        let stack = &mut engine.transforms.stack;
+        let combined = transform.then(stack.last().copied().unwrap_or_default());
+        stack.push(combined);
+    })
+}
+
+/// Pop the last transform pushed with [`push_transform`]. Does nothing if the stack is empty.
+pub fn pop_transform() {
+    with_engine_mut(|engine| {
+        engine.transforms.stack.pop();
+    })
+}
+
+/// Get the transform currently applied to everything drawn, combining the transform stack and the
+/// camera.
+pub fn transform() -> Transform {
+    with_engine(current)
+}
+
+/// Convert a point in world coordinates to drawing coordinates, using the current camera and
+/// transform stack.
+pub fn world_to_screen(p: Point) -> Point {
+    transform().apply_point(p)
+}
+
+/// Convert a point in drawing coordinates, such as [`mouse_pos`][crate::mouse_pos], to world
+/// coordinates, using the current camera and transform stack. If the transform can't be undone,
+/// such as when the camera's zoom is 0, the point is returned unchanged.
+pub fn screen_to_world(p: Point) -> Point {
+    transform().inverse().map_or(p, |t| t.apply_point(p))
+}
+
+/// Draw the outlines of rectangles, transformed.
+pub(crate) fn draw_rects(
+    canvas: &mut WindowCanvas,
+    transform: Transform,
+    rects: &[Rect],
+) -> Result<(), String> {
+    if let Some(rects) = transform_rects(transform, rects) {
+        return canvas.draw_rects(&rects);
+    }
+    for &rect in rects {
+        let corners = transform.corners(rect.into());
+        let mut points: Vec<Point> = corners.iter().map(|&p| round(p)).collect();
+        points.push(points[0]);
+        canvas.draw_lines(points.as_slice())?;
+    }
+    Ok(())
+}
+
+/// Fill rectangles, transformed.
+pub(crate) fn fill_rects(
+    canvas: &mut WindowCanvas,
+    transform: Transform,
+    rects: &[Rect],
+) -> Result<(), String> {
+    if let Some(rects) = transform_rects(transform, rects) {
+        return canvas.fill_rects(&rects);
+    }
+    let spans: Vec<Rect> = rects
+        .iter()
+        .flat_map(|&rect| spans(&transform.corners(rect.into())))
+        .collect();
+    if spans.is_empty() {
+        return Ok(());
+    }
+    canvas.fill_rects(&spans)
+}
+
+fn transform_rects(transform: Transform, rects: &[Rect]) -> Option<Vec<Rect>> {
+    rects.iter().map(|&rect| transform.rect(rect)).collect()
+}
+
+fn round((x, y): (f32, f32)) -> Point {
+    Point::new(x.round() as i32, y.round() as i32)
+}
+
+/// Get the rows of pixels covered by a convex polygon, as rectangles one pixel high. A pixel is
+/// covered if its center is inside the polygon.
+pub(crate) fn spans(polygon: &[(f32, f32)]) -> Vec<Rect> {
+    let top = polygon.iter().map(|p| p.1).fold(f32::INFINITY, f32::min);
+    let bottom = polygon
+        .iter()
+        .map(|p| p.1)
+        .fold(f32::NEG_INFINITY, f32::max);
+    if !top.is_finite() || !bottom.is_finite() {
+        return Vec::new();
+    }
+
+    let mut spans = Vec::new();
+    for y in (top - 0.5).ceil() as i32..(bottom - 0.5).ceil() as i32 {
+        let center = y as f32 + 0.5;
+        let mut left = f32::INFINITY;
+        let mut right = f32::NEG_INFINITY;
+        let edges = polygon.iter().zip(polygon.iter().cycle().skip(1));
+        for (&(x1, y1), &(x2, y2)) in edges {
+            if (y1 <= center) != (y2 <= center) {
+                let x = x1 + (center - y1) / (y2 - y1) * (x2 - x1);
+                left = left.min(x);
+                right = right.max(x);
+            }
+        }
+        let (start, end) = ((left - 0.5).ceil() as i32, (right - 0.5).ceil() as i32);
+        if end > start {
+            spans.push(Rect::new(start, y, (end - start) as u32, 1));
+        }
+    }
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_util, Color};
+
+    /// Transforms combine in order, and can be undone.
+    #[test]
+    fn test_transform() {
+        let t = Transform::scaling(2.0, 2.0).then(Transform::translation(10.0, 0.0));
+        assert_eq!(t.apply(1.0, 1.0), (12.0, 2.0));
+        assert_eq!(t.inverse().unwrap().apply(12.0, 2.0), (1.0, 1.0));
+        assert_eq!(Transform::scaling(0.0, 1.0).inverse(), None);
+
+        let (x, y) = Transform::rotation(90.0).apply(1.0, 0.0);
+        assert!(x.abs() < 1e-6 && (y - 1.0).abs() < 1e-6);
+
+        // The camera's position is shown at the center
+        let camera = Camera {
+            zoom: 2.0,
+            ..Camera::new(100.0, 50.0)
+        };
+        let t = camera.transform((64, 32));
+        assert_eq!(t.apply(100.0, 50.0), (32.0, 16.0));
+        assert_eq!(t.apply(101.0, 50.0), (34.0, 16.0));
+    }
+
+    /// The camera and transform stack apply to shapes.
+    #[test]
+    fn test_transformed_drawing() {
+        test_util::check_frame(
+            16,
+            16,
+            || {
+                crate::clear(Color::BLACK);
+                set_camera(Camera::new(8.0, 8.0));
+                push_transform(Transform::translation(4.0, 0.0));
+                assert_eq!(world_to_screen(Point::new(0, 0)), Point::new(4, 0));
+                assert_eq!(screen_to_world(Point::new(4, 0)), Point::new(0, 0));
+                crate::fill_rect(Rect::new(0, 0, 2, 2), Color::RED)?;
+                pop_transform();
+
+                // A square rotated by 45 degrees covers its center but not its corners
+                push_transform(Transform::rotation(45.0).then(Transform::translation(4.0, 12.0)));
+                let res = crate::fill_rect(Rect::new(-2, -2, 4, 4), Color::GREEN);
+                set_camera(None);
+                res
+            },
+            |shot| {
+                assert_eq!(shot.pixel(4, 0), Some(Color::RED));
+                assert_eq!(shot.pixel(5, 1), Some(Color::RED));
+                assert_eq!(shot.pixel(6, 0), Some(Color::BLACK));
+                assert_eq!(shot.pixel(4, 12), Some(Color::GREEN));
+                assert_eq!(shot.pixel(2, 10), Some(Color::BLACK));
+            },
+        );
+    }
+}
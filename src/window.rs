@@ -0,0 +1,122 @@
+use sdl2::{sys, video::WindowPos};
+
+use crate::{with_engine, with_engine_mut, Fullscreen, Point};
+
+/// Get the current fullscreen state of the window.
+pub fn fullscreen() -> Fullscreen {
+    with_engine(|e| e.canvas.window().fullscreen_state().into())
+}
+
+/// Set the fullscreen state of the window.
+///
+/// You can either pass a bool, indicating whether you want fullscreen or not, or a variant of
+/// [`Fullscreen`][crate::Fullscreen] if, for example, you want to use desktop mode.
+pub fn set_fullscreen(val: impl Into<Fullscreen>) -> Result<(), String> {
+    let val = val.into();
+    with_engine_mut(|e| e.canvas.window_mut().set_fullscreen(val.into()))
+}
+
+/// Maximize the window.
+pub fn maximize_window() {
+    with_engine_mut(|e| e.canvas.window_mut().maximize())
+}
+
+/// Minimize the window.
+pub fn minimize_window() {
+    with_engine_mut(|e| e.canvas.window_mut().minimize())
+}
+
+/// Returns whether the window can be resized by the user.
+pub fn resizable() -> bool {
+    with_engine(|e| {
+        e.canvas.window().window_flags() & sys::SDL_WindowFlags::SDL_WINDOW_RESIZABLE as u32 != 0
+    })
+}
+
+/// Restore the size and position of a minimized or maximized window.
+pub fn restore_window() {
+    with_engine_mut(|e| e.canvas.window_mut().restore())
+}
+
+/// Set whether the window can be resized by the user.
+pub fn set_resizable(resizable: bool) {
+    with_engine_mut(|e| {
+        let resizable = if resizable {
+            sys::SDL_bool::SDL_TRUE
+        } else {
+            sys::SDL_bool::SDL_FALSE
+        };
+        // Safety: The window is valid for as long as the engine exists.
+        unsafe { sys::SDL_SetWindowResizable(e.canvas.window().raw(), resizable) }
+    })
+}
+
+/// Set the title of the window. If the FPS is being shown in the title bar, it is added to the end
+/// of this title.
+pub fn set_title(title: &str) -> Result<(), String> {
+    with_engine_mut(|e| {
+        e.title = title.to_string();
+        e.refresh_title()
+    })
+}
+
+/// Move the window so its top-left corner is at `pos` on the screen.
+pub fn set_window_position<P: Into<Point>>(pos: P) {
+    let pos = pos.into();
+    with_engine_mut(|e| {
+        e.canvas.window_mut().set_position(
+            WindowPos::Positioned(pos.x()),
+            WindowPos::Positioned(pos.y()),
+        )
+    })
+}
+
+/// Set the size of the window's client area.
+pub fn set_window_size(width: u32, height: u32) -> Result<(), String> {
+    with_engine_mut(|e| {
+        e.canvas
+            .window_mut()
+            .set_size(width, height)
+            .map_err(|e| e.to_string())
+    })
+}
+
+/// Get the title of the window, without any FPS counter.
+pub fn title() -> String {
+    with_engine(|e| e.title.clone())
+}
+
+/// Get the position of the window's top-left corner on the screen.
+pub fn window_position() -> Point {
+    with_engine(|e| e.canvas.window().position().into())
+}
+
+/// Get the size of the window's client area.
+pub fn window_size() -> (u32, u32) {
+    with_engine(|e| e.canvas.window().size())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_util, Builder};
+
+    /// The window can be changed while the engine is running.
+    #[test]
+    fn test_window_control() {
+        test_util::run(|| {
+            let mut app = |_| {
+                set_title("New title")?;
+                assert_eq!(title(), "New title");
+                set_window_size(32, 24)?;
+                assert_eq!(window_size(), (32, 24));
+                Ok(true)
+            };
+            Builder::new("Test", 64, 64)
+                .headless(true)
+                .max_frames(1)
+                .start(&mut app)
+                .unwrap();
+        });
+    }
+}